@@ -41,6 +41,8 @@ pub enum AmmError {
     InsufficientBalance,
     #[msg("Zero balance.")]
     ZeroBalance,
+    #[msg("User has no access to this permissioned pool.")]
+    AccessDenied,
 }
 
 impl From<CurveError> for AmmError {
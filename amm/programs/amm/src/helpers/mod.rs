@@ -25,12 +25,24 @@ macro_rules! assert_not_expired {
     };
 }
 
+#[macro_export]
+macro_rules! assert_has_access {
+    ($permissioned:expr, $access:expr) => {
+        if $permissioned && $access.is_none() {
+            return err!(AmmError::AccessDenied);
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! has_update_authority {
     ($x:expr) => {
+        $crate::has_update_authority!($x, user)
+    };
+    ($x:expr, $signer:ident) => {
         match $x.config.authority {
             Some(a) => {
-                require_keys_eq!(a, $x.user.key(), AmmError::InvalidAuthority);
+                require_keys_eq!(a, $x.$signer.key(), AmmError::InvalidAuthority);
             }
             None => return err!(AmmError::NoAuthoritySet),
         }
@@ -6,9 +6,9 @@ use anchor_spl::{
     },
 };
 use constant_product_curve::ConstantProduct;
-use crate::{assert_non_zero, assert_not_expired, assert_not_locked};
+use crate::{assert_has_access, assert_non_zero, assert_not_expired, assert_not_locked};
 use crate::error::AmmError;
-use crate::state::config::Config;
+use crate::state::{access::Access, config::Config};
 
 #[derive(Accounts)]
 pub struct Deposit<'info> {
@@ -67,6 +67,11 @@ pub struct Deposit<'info> {
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"access", config.key().as_ref(), user.key().as_ref()],
+        bump = access.bump,
+    )]
+    pub access: Option<Account<'info, Access>>,
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -75,6 +80,7 @@ pub struct Deposit<'info> {
 impl<'info> Deposit<'info> {
     pub fn deposit(&mut self, amount: u64, max_x: u64, max_y: u64, expiration: i64) -> Result<()> {
         assert_not_locked!(self.config.locked);
+        assert_has_access!(self.config.permissioned, self.access);
         assert_not_expired!(expiration);
         assert_non_zero!([amount, max_x, max_y]);
 
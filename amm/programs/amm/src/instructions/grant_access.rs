@@ -0,0 +1,39 @@
+use crate::error::AmmError;
+use crate::has_update_authority;
+use crate::state::{access::Access, config::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct GrantAccess<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"access", config.key().as_ref(), user.as_ref()],
+        bump,
+        space = Access::INIT_SPACE,
+    )]
+    pub access: Account<'info, Access>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> GrantAccess<'info> {
+    pub fn grant_access(&mut self, bumps: &GrantAccessBumps, user: Pubkey) -> Result<()> {
+        has_update_authority!(self, authority);
+
+        self.access.set_inner(Access {
+            config: self.config.key(),
+            user,
+            bump: bumps.access,
+        });
+
+        Ok(())
+    }
+}
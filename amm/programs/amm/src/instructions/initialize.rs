@@ -19,7 +19,7 @@ pub struct Initialize<'info> {
         seeds = [b"lp", config.key.as_ref()],
         bump,
         mint::decimals = 6,
-        mint::authority = config,
+        mint::authority = auth,
     )]
     pub mint_lp: Account<'info, Mint>,
     #[account(
@@ -57,8 +57,10 @@ pub struct Initialize<'info> {
 
 
 impl<'info> Initialize<'info> {
-    pub fn init(&mut self, bumps: &InitializeBumps, seed: u64, fee: u16, authority: Option<Pubkey>,) -> Result<()> {
+    pub fn init(&mut self, bumps: &InitializeBumps, seed: u64, fee: u16, authority: Option<Pubkey>, permissioned: bool,) -> Result<()> {
         require!(fee <= 10000, AmmError::FeePercentErr);
+        // a permissioned pool needs someone to issue access entries
+        require!(!permissioned || authority.is_some(), AmmError::NoAuthoritySet);
         self.config.set_inner(Config {
             seed,
             authority,
@@ -66,6 +68,7 @@ impl<'info> Initialize<'info> {
             mint_y: self.mint_y.key(),
             fee,
             locked: false,
+            permissioned,
             config_bump: bumps.config,
             lp_bump: bumps.mint_lp,
             auth_bump: bumps.auth,
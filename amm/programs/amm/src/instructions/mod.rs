@@ -8,4 +8,10 @@ pub mod withdraw;
 pub use withdraw::*;
 
 pub mod initialize;
-pub use initialize::*;
\ No newline at end of file
+pub use initialize::*;
+
+pub mod grant_access;
+pub use grant_access::*;
+
+pub mod revoke_access;
+pub use revoke_access::*;
@@ -0,0 +1,32 @@
+use crate::error::AmmError;
+use crate::has_update_authority;
+use crate::state::{access::Access, config::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct RevokeAccess<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = config,
+        close = authority,
+        seeds = [b"access", config.key().as_ref(), access.user.as_ref()],
+        bump = access.bump,
+    )]
+    pub access: Account<'info, Access>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RevokeAccess<'info> {
+    pub fn revoke_access(&mut self) -> Result<()> {
+        // the access entry itself is closed by the `close` constraint
+        has_update_authority!(self, authority);
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::AmmError;
-use crate::state::config::Config;
-use crate::{assert_non_zero, assert_not_expired, assert_not_locked};
+use crate::state::{access::Access, config::Config};
+use crate::{assert_has_access, assert_non_zero, assert_not_expired, assert_not_locked};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
@@ -60,6 +60,11 @@ pub struct Swap<'info> {
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"access", config.key().as_ref(), user.key().as_ref()],
+        bump = access.bump,
+    )]
+    pub access: Option<Account<'info, Access>>,
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -68,6 +73,7 @@ pub struct Swap<'info> {
 impl<'info> Swap<'info> {
     pub fn swap(&mut self, is_x: bool, amount: u64, min: u64, expiration: i64) -> Result<()> {
         assert_not_locked!(self.config.locked);
+        assert_has_access!(self.config.permissioned, self.access);
         assert_not_expired!(expiration);
         assert_non_zero!([amount]);
 
@@ -13,8 +13,8 @@ declare_id!("GVEEGPqjtDmP1umTyWNS9QFqdh1xzyqkdNRRrkfgG71C");
 pub mod amm {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>, seed: u64, fee: u16, authority: Option<Pubkey>,) -> Result<()> {
-        ctx.accounts.init(&ctx.bumps, seed, fee, authority)?;
+    pub fn initialize(ctx: Context<Initialize>, seed: u64, fee: u16, authority: Option<Pubkey>, permissioned: bool,) -> Result<()> {
+        ctx.accounts.init(&ctx.bumps, seed, fee, authority, permissioned)?;
         Ok(())
     }
 
@@ -33,7 +33,14 @@ pub mod amm {
         Ok(())
     }
 
+    pub fn grant_access(ctx: Context<GrantAccess>, user: Pubkey,) -> Result<()> {
+        ctx.accounts.grant_access(&ctx.bumps, user)?;
+        Ok(())
+    }
 
-    
+    pub fn revoke_access(ctx: Context<RevokeAccess>) -> Result<()> {
+        ctx.accounts.revoke_access()?;
+        Ok(())
+    }
 }
 
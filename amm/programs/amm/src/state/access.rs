@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Access {
+    pub config: Pubkey, // pool this entry grants access to
+    pub user: Pubkey, // wallet allowed to deposit and swap
+    pub bump: u8,
+}
+
+impl Space for Access {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 1;
+}
@@ -9,6 +9,7 @@ pub struct Config {
     pub mint_y: Pubkey, // mint of token y
     pub fee: u16, // fee in basis points
     pub locked: bool,
+    pub permissioned: bool, // deposit and swap require an access entry issued by the authority
     pub config_bump: u8, // bump for the config account
     pub auth_bump: u8, // bump for the auth account
 }
@@ -22,5 +23,8 @@ impl Space for Config {
     
     // u16 -> 2 bytes
     // Option<u16> -> 3 bytes but since this is odd, it must be padded to an even number
-    const INIT_SPACE: usize = 8 + 8 + (1 + 32) + 32 + 32 + 2 + 1 + 1 + 1;
+
+    // discriminator, then every field in declaration order: authority, seed,
+    // lp_bump, mint_x, mint_y, fee, locked, permissioned, config_bump, auth_bump
+    const INIT_SPACE: usize = 8 + (1 + 32) + 8 + 1 + 32 + 32 + 2 + 1 + 1 + 1 + 1;
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
 pub mod config;
+pub mod access;
 
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use access::*;
@@ -1,16 +1,209 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { AnchorError, BN, Program } from "@coral-xyz/anchor";
 import { Amm } from "../target/types/amm";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import { randomBytes } from "crypto";
 
 describe("amm", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+  const connection = provider.connection;
 
   const program = anchor.workspace.Amm as Program<Amm>;
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  // the provider wallet creates the pools and is the authority of the permissioned one
+  const admin = (provider.wallet as anchor.Wallet).payer;
+  const user = Keypair.generate();
+
+  const [auth] = PublicKey.findProgramAddressSync([Buffer.from("auth")], program.programId);
+
+  type Pool = {
+    seed: BN;
+    config: PublicKey;
+    mintLp: PublicKey;
+    mintX: PublicKey;
+    mintY: PublicKey;
+  };
+
+  // vaults are the auth PDA's ATAs, so every pool needs its own pair of mints
+  const pool = async (): Promise<Pool> => {
+    const seed = new BN(randomBytes(8));
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [mintLp] = PublicKey.findProgramAddressSync([Buffer.from("lp"), config.toBuffer()], program.programId);
+    const mintX = await createMint(connection, admin, admin.publicKey, null, 6);
+    const mintY = await createMint(connection, admin, admin.publicKey, null, 6);
+    for (const mint of [mintX, mintY]) {
+      const ata = await getOrCreateAssociatedTokenAccount(connection, admin, mint, user.publicKey);
+      await mintTo(connection, admin, mint, ata.address, admin, 1_000 * 10 ** 6);
+    }
+    return { seed, config, mintLp, mintX, mintY };
+  };
+
+  const accessOf = (p: Pool) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("access"), p.config.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    )[0];
+
+  const initialize = (p: Pool, authority: PublicKey | null, permissioned: boolean) =>
+    program.methods
+      .initialize(p.seed, 30, authority, permissioned)
+      .accountsPartial({
+        initializer: admin.publicKey,
+        mintX: p.mintX,
+        mintY: p.mintY,
+        mintLp: p.mintLp,
+        vaultX: getAssociatedTokenAddressSync(p.mintX, auth, true),
+        vaultY: getAssociatedTokenAddressSync(p.mintY, auth, true),
+        auth,
+        config: p.config,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+  const poolAccounts = (p: Pool, access: PublicKey | null) => ({
+    user: user.publicKey,
+    mintX: p.mintX,
+    mintY: p.mintY,
+    mintLp: p.mintLp,
+    vaultX: getAssociatedTokenAddressSync(p.mintX, auth, true),
+    vaultY: getAssociatedTokenAddressSync(p.mintY, auth, true),
+    userX: getAssociatedTokenAddressSync(p.mintX, user.publicKey),
+    userY: getAssociatedTokenAddressSync(p.mintY, user.publicKey),
+    auth,
+    config: p.config,
+    access,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+  });
+
+  const expiration = () => new BN(Math.floor(Date.now() / 1000) + 60 * 60);
+
+  const deposit = (p: Pool, access: PublicKey | null) =>
+    program.methods
+      .deposit(new BN(1_000_000), new BN(10_000_000), new BN(10_000_000), expiration())
+      .accountsPartial({
+        ...poolAccounts(p, access),
+        userLp: getAssociatedTokenAddressSync(p.mintLp, user.publicKey),
+      })
+      .signers([user])
+      .rpc();
+
+  const swap = (p: Pool, access: PublicKey | null) =>
+    program.methods
+      .swap(true, new BN(100_000), new BN(1), expiration())
+      .accountsPartial(poolAccounts(p, access))
+      .signers([user])
+      .rpc();
+
+  const grantAccess = (p: Pool, authority: Keypair) =>
+    program.methods
+      .grantAccess(user.publicKey)
+      .accountsPartial({
+        authority: authority.publicKey,
+        config: p.config,
+        access: accessOf(p),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+  const revokeAccess = (p: Pool, authority: Keypair) =>
+    program.methods
+      .revokeAccess()
+      .accountsPartial({
+        authority: authority.publicKey,
+        config: p.config,
+        access: accessOf(p),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+  const expectError = async (tx: Promise<string>, code: string) => {
+    try {
+      await tx;
+    } catch (err) {
+      expect(err).to.be.instanceOf(AnchorError);
+      expect((err as AnchorError).error.errorCode.code).to.equal(code);
+      return;
+    }
+    expect.fail(`expected ${code}`);
+  };
+
+  let gated: Pool;
+  let open: Pool;
+
+  before("Create Accounts", async () => {
+    const airdrop = await connection.requestAirdrop(user.publicKey, 2 * LAMPORTS_PER_SOL);
+    await connection.confirmTransaction({ signature: airdrop, ...(await connection.getLatestBlockhash()) });
+
+    gated = await pool();
+    open = await pool();
+  });
+
+  it("refuses a permissioned pool without an authority", async () => {
+    await expectError(initialize(gated, null, true), "NoAuthoritySet");
+  });
+
+  it("initializes a permissioned and a permissionless pool", async () => {
+    await initialize(gated, admin.publicKey, true);
+    await initialize(open, null, false);
+
+    const config = await program.account.config.fetch(gated.config);
+    expect(config.permissioned).to.equal(true);
+    expect(config.authority?.equals(admin.publicKey)).to.equal(true);
+    expect((await program.account.config.fetch(open.config)).permissioned).to.equal(false);
+  });
+
+  it("lets anyone deposit and swap in a permissionless pool", async () => {
+    await deposit(open, null);
+    await swap(open, null);
+  });
+
+  it("denies deposit and swap without an access entry", async () => {
+    await expectError(deposit(gated, null), "AccessDenied");
+    await expectError(swap(gated, null), "AccessDenied");
+  });
+
+  it("only lets the authority grant access", async () => {
+    await expectError(grantAccess(gated, user), "InvalidAuthority");
+    await grantAccess(gated, admin);
+
+    const access = await program.account.access.fetch(accessOf(gated));
+    expect(access.config.equals(gated.config)).to.equal(true);
+    expect(access.user.equals(user.publicKey)).to.equal(true);
+  });
+
+  it("allows deposit and swap with an access entry", async () => {
+    await deposit(gated, accessOf(gated));
+    await swap(gated, accessOf(gated));
+  });
+
+  it("only lets the authority revoke access", async () => {
+    await expectError(revokeAccess(gated, user), "InvalidAuthority");
+    await revokeAccess(gated, admin);
+    expect(await program.account.access.fetchNullable(accessOf(gated))).to.equal(null);
+  });
+
+  it("denies deposit and swap once access is revoked", async () => {
+    await expectError(deposit(gated, null), "AccessDenied");
+    await expectError(swap(gated, null), "AccessDenied");
   });
 });
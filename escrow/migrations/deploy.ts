@@ -4,9 +4,27 @@
 
 const anchor = require("@coral-xyz/anchor");
 
+// `take` needs the fee config, so it is created right after the first deploy.
+// The provider wallet must be the program's upgrade authority; it becomes the
+// config admin. FEE_BPS and TREASURY override the defaults below.
 module.exports = async function (provider) {
   // Configure client to use the provider.
   anchor.setProvider(provider);
 
-  // Add your deploy script here.
+  const program = anchor.workspace.Escrow;
+  const [config] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("config")], program.programId);
+  if (await provider.connection.getAccountInfo(config)) {
+    return;
+  }
+
+  const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111"),
+  );
+  const feeBps = Number(process.env.FEE_BPS ?? 0);
+  const treasury = new anchor.web3.PublicKey(process.env.TREASURY ?? provider.wallet.publicKey);
+  await program.methods
+    .initializeConfig(feeBps, treasury)
+    .accountsPartial({ admin: provider.wallet.publicKey, programData, config })
+    .rpc();
 };
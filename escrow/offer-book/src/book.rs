@@ -0,0 +1,217 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use escrow::state::{BasketLeg, DutchAuction, EscrowState};
+
+use crate::events::{parse_logs, EscrowEvent};
+
+/// An open offer: the maker gives `deposit_amount` of `mint_a`, plus the
+/// `offered` legs, for `receive_amount` of `mint_b` plus the `requested` legs.
+#[derive(Clone)]
+pub struct Offer {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+    pub offered: Vec<BasketLeg>,
+    pub requested: Vec<BasketLeg>,
+    pub expires_at: i64,
+    pub taker: Option<Pubkey>,
+    pub auction: Option<DutchAuction>,
+}
+
+impl Offer {
+    pub fn is_expired(&self, now: i64) -> bool {
+        EscrowState::expired(self.expires_at, self.auction.as_ref(), now)
+    }
+
+    /// Basket offers swap every leg at once and cannot be partially filled.
+    pub fn is_basket(&self) -> bool {
+        !self.offered.is_empty() || !self.requested.is_empty()
+    }
+
+    /// Mint B asked for the remaining deposit at `now`, following the
+    /// program's auction pricing.
+    pub fn receive_amount_at(&self, now: i64) -> u64 {
+        match &self.auction {
+            Some(auction) => auction.price(self.receive_amount, now),
+            None => self.receive_amount,
+        }
+    }
+
+    pub fn can_take(&self, taker: &Pubkey) -> bool {
+        self.taker.is_none() || self.taker == Some(*taker)
+    }
+
+    /// Orders offers by the mint B asked per unit of mint A, cheapest first.
+    /// Auctions are ranked by their start price; use `receive_amount_at` for
+    /// the live price. Basket legs are not priced.
+    pub fn cmp_price(&self, other: &Offer) -> Ordering {
+        let lhs = self.receive_amount as u128 * other.deposit_amount as u128;
+        let rhs = other.receive_amount as u128 * self.deposit_amount as u128;
+        lhs.cmp(&rhs)
+    }
+
+    fn from_state(escrow: Pubkey, state: &EscrowState) -> Self {
+        Self {
+            escrow,
+            maker: state.maker,
+            mint_a: state.mint_a,
+            mint_b: state.mint_b,
+            deposit_amount: state.deposit_amount,
+            receive_amount: state.receive_amount,
+            offered: state.offered.clone(),
+            requested: state.requested.clone(),
+            expires_at: state.expires_at,
+            taker: state.taker,
+            auction: state.auction.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct OfferBook {
+    offers: HashMap<Pubkey, Offer>,
+    pairs: BTreeMap<(Pubkey, Pubkey), BTreeSet<Pubkey>>,
+}
+
+impl OfferBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offers.is_empty()
+    }
+
+    pub fn get(&self, escrow: &Pubkey) -> Option<&Offer> {
+        self.offers.get(escrow)
+    }
+
+    /// Every `(mint_a, mint_b)` pair with at least one open offer.
+    pub fn pairs(&self) -> impl Iterator<Item = &(Pubkey, Pubkey)> {
+        self.pairs.keys()
+    }
+
+    /// Open offers giving `mint_a` for `mint_b`, cheapest first.
+    pub fn offers(&self, mint_a: &Pubkey, mint_b: &Pubkey) -> Vec<&Offer> {
+        let mut offers: Vec<&Offer> = self
+            .pairs
+            .get(&(*mint_a, *mint_b))
+            .into_iter()
+            .flatten()
+            .filter_map(|escrow| self.offers.get(escrow))
+            .collect();
+        offers.sort_by(|a, b| a.cmp_price(b).then_with(|| a.escrow.cmp(&b.escrow)));
+        offers
+    }
+
+    /// Applies an account snapshot. Closed accounts (empty data), escrows
+    /// whose vesting already started, arbitrated and milestone escrows are
+    /// not open offers and are dropped.
+    /// Data that is not an `EscrowState` is ignored.
+    pub fn apply_account(&mut self, escrow: Pubkey, data: &[u8]) {
+        if data.is_empty() {
+            self.remove(&escrow);
+            return;
+        }
+        let Ok(state) = EscrowState::try_deserialize(&mut &data[..]) else {
+            return;
+        };
+        if state.is_vesting() || state.has_beneficiary() {
+            self.remove(&escrow);
+        } else {
+            self.insert(Offer::from_state(escrow, &state));
+        }
+    }
+
+    /// Applies the events found in a transaction's log messages, in order.
+    pub fn apply_logs<S: AsRef<str>>(&mut self, logs: &[S]) {
+        for event in parse_logs(logs) {
+            self.apply_event(event);
+        }
+    }
+
+    pub fn apply_event(&mut self, event: EscrowEvent) {
+        match event {
+            EscrowEvent::Created(e) if e.beneficiary.is_some() => {}
+            EscrowEvent::Created(e) => self.insert(Offer {
+                escrow: e.escrow,
+                maker: e.maker,
+                mint_a: e.mint_a,
+                mint_b: e.mint_b,
+                deposit_amount: e.deposit_amount,
+                receive_amount: e.receive_amount,
+                offered: e.offered,
+                requested: e.requested,
+                expires_at: e.expires_at,
+                taker: e.taker,
+                auction: e.auction,
+            }),
+            EscrowEvent::Taken(e) => {
+                if e.remaining_deposit == 0 {
+                    self.remove(&e.escrow);
+                } else if let Some(offer) = self.offers.get_mut(&e.escrow) {
+                    offer.deposit_amount = e.remaining_deposit;
+                    offer.receive_amount = e.remaining_receive;
+                }
+            }
+            EscrowEvent::Refunded(e) => {
+                self.remove(&e.escrow);
+            }
+            EscrowEvent::Updated(e) => {
+                if let Some(offer) = self.offers.get_mut(&e.escrow) {
+                    offer.deposit_amount = e.deposit_amount;
+                    offer.receive_amount = e.receive_amount;
+                    offer.expires_at = e.expires_at;
+                }
+            }
+        }
+    }
+
+    /// Drops the offers that can no longer be taken at `now`. They stay
+    /// on chain until the maker or a crank refunds them.
+    pub fn prune_expired(&mut self, now: i64) {
+        let expired: Vec<Pubkey> = self
+            .offers
+            .values()
+            .filter(|offer| offer.is_expired(now))
+            .map(|offer| offer.escrow)
+            .collect();
+        for escrow in expired {
+            self.remove(&escrow);
+        }
+    }
+
+    fn insert(&mut self, offer: Offer) {
+        if let Some(old) = self.offers.get(&offer.escrow) {
+            if (old.mint_a, old.mint_b) != (offer.mint_a, offer.mint_b) {
+                let escrow = offer.escrow;
+                self.remove(&escrow);
+            }
+        }
+        self.pairs.entry((offer.mint_a, offer.mint_b)).or_default().insert(offer.escrow);
+        self.offers.insert(offer.escrow, offer);
+    }
+
+    fn remove(&mut self, escrow: &Pubkey) -> Option<Offer> {
+        let offer = self.offers.remove(escrow)?;
+        let pair = (offer.mint_a, offer.mint_b);
+        if let Some(escrows) = self.pairs.get_mut(&pair) {
+            escrows.remove(escrow);
+            if escrows.is_empty() {
+                self.pairs.remove(&pair);
+            }
+        }
+        Some(offer)
+    }
+}
@@ -0,0 +1,76 @@
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use escrow::events::{OfferCreated, OfferRefunded, OfferTaken, OfferUpdated};
+
+const PROGRAM_DATA: &str = "Program data: ";
+
+pub enum EscrowEvent {
+    Created(Box<OfferCreated>),
+    Taken(OfferTaken),
+    Refunded(OfferRefunded),
+    Updated(OfferUpdated),
+}
+
+impl EscrowEvent {
+    /// Decodes an event from the bytes the program logged, discriminator
+    /// included. Returns `None` for anything that is not an escrow event.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (discriminator, mut payload) = data.split_at(8);
+        let event = if discriminator == OfferCreated::DISCRIMINATOR {
+            Self::Created(Box::new(OfferCreated::deserialize(&mut payload).ok()?))
+        } else if discriminator == OfferTaken::DISCRIMINATOR {
+            Self::Taken(OfferTaken::deserialize(&mut payload).ok()?)
+        } else if discriminator == OfferRefunded::DISCRIMINATOR {
+            Self::Refunded(OfferRefunded::deserialize(&mut payload).ok()?)
+        } else if discriminator == OfferUpdated::DISCRIMINATOR {
+            Self::Updated(OfferUpdated::deserialize(&mut payload).ok()?)
+        } else {
+            return None;
+        };
+        Some(event)
+    }
+}
+
+/// Extracts the escrow events from a transaction's log messages.
+///
+/// Only `Program data:` lines logged while the escrow program is the one
+/// executing are decoded, so events of other programs invoked in the same
+/// transaction are never mistaken for ours.
+pub fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Vec<EscrowEvent> {
+    let escrow_id = escrow::ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs {
+        let line = line.as_ref();
+        if let Some(data) = line.strip_prefix(PROGRAM_DATA) {
+            if stack.last() != Some(&escrow_id.as_str()) {
+                continue;
+            }
+            if let Some(event) = STANDARD.decode(data).ok().and_then(|bytes| EscrowEvent::decode(&bytes)) {
+                events.push(event);
+            }
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        if words.next() != Some("Program") {
+            continue;
+        }
+        let (Some(program), Some(status)) = (words.next(), words.next()) else {
+            continue;
+        };
+        match status {
+            "invoke" => stack.push(program),
+            "success" | "failed:" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    events
+}
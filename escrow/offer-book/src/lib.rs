@@ -0,0 +1,12 @@
+//! Keeps an in-memory book of open escrow offers, grouped by mint pair.
+//!
+//! The book can be fed either account snapshots of `EscrowState` (e.g. from
+//! `getProgramAccounts` or an account subscription) or the logs of confirmed
+//! transactions, from which the program's events are decoded. Both sources can
+//! be mixed: a snapshot always replaces what the book knows about an offer.
+
+mod book;
+mod events;
+
+pub use book::{Offer, OfferBook};
+pub use events::{parse_logs, EscrowEvent};
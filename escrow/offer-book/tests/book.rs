@@ -0,0 +1,177 @@
+//! Feeds transaction logs recorded from the escrow program, and account
+//! snapshots, through the offer book.
+
+use anchor_lang::{prelude::Pubkey, AccountSerialize};
+use escrow::state::{BasketLeg, DutchAuction, EscrowState};
+use offer_book::{parse_logs, EscrowEvent, OfferBook};
+
+const MAKE: &str = include_str!("logs/make.log");
+const TAKE_PARTIAL: &str = include_str!("logs/take_partial.log");
+const UPDATE_OFFER: &str = include_str!("logs/update_offer.log");
+const TAKE_FINAL: &str = include_str!("logs/take_final.log");
+const MAKE_BASKET: &str = include_str!("logs/make_basket.log");
+const REFUND_BASKET: &str = include_str!("logs/refund_basket.log");
+
+/// `expires_at` of the recorded offers.
+const EXPIRES_AT: i64 = 1_900_000_000;
+
+fn lines(logs: &str) -> Vec<&str> {
+    logs.lines().collect()
+}
+
+fn created(logs: &str) -> Box<escrow::events::OfferCreated> {
+    match parse_logs(&lines(logs)).pop() {
+        Some(EscrowEvent::Created(e)) => e,
+        _ => panic!("no OfferCreated in the logs"),
+    }
+}
+
+#[test]
+fn parse_logs_decodes_the_recorded_events() {
+    let make = created(MAKE);
+    assert_eq!((make.deposit_amount, make.receive_amount, make.expires_at), (1_000_000, 2_000_000, EXPIRES_AT));
+    assert!(make.offered.is_empty() && make.requested.is_empty());
+
+    let [EscrowEvent::Taken(take)] = &parse_logs(&lines(TAKE_PARTIAL))[..] else {
+        panic!("expected one OfferTaken");
+    };
+    assert_eq!(take.escrow, make.escrow);
+    assert_eq!((take.amount, take.cost), (250_000, 500_000));
+    assert_eq!((take.remaining_deposit, take.remaining_receive), (750_000, 1_500_000));
+
+    let [EscrowEvent::Updated(update)] = &parse_logs(&lines(UPDATE_OFFER))[..] else {
+        panic!("expected one OfferUpdated");
+    };
+    assert_eq!((update.deposit_amount, update.receive_amount), (750_000, 1_200_000));
+    assert_eq!(update.expires_at, EXPIRES_AT + 3600);
+
+    let [EscrowEvent::Refunded(refund)] = &parse_logs(&lines(REFUND_BASKET))[..] else {
+        panic!("expected one OfferRefunded");
+    };
+    assert_eq!(refund.deposit_amount, 1_000_000);
+}
+
+#[test]
+fn parse_logs_ignores_data_logged_by_other_programs() {
+    let mut logs = lines(MAKE);
+    let data = *logs.iter().find(|line| line.starts_with("Program data: ")).unwrap();
+    // the same bytes logged from inside the token program's CPI frame
+    let cpi = logs.iter().position(|line| line.starts_with("Program Tokenkeg") && line.contains("invoke")).unwrap();
+    logs.insert(cpi + 1, data);
+    assert_eq!(parse_logs(&logs).len(), 1);
+
+    // and from a top-level instruction of another program
+    let other = ["Program 11111111111111111111111111111111 invoke [1]", data, "Program 11111111111111111111111111111111 success"];
+    assert!(parse_logs(&other).is_empty());
+}
+
+#[test]
+fn book_follows_an_offer_through_its_logs() {
+    let make = created(MAKE);
+    let mut book = OfferBook::new();
+    book.apply_logs(&lines(MAKE));
+    assert_eq!(book.pairs().collect::<Vec<_>>(), [&(make.mint_a, make.mint_b)]);
+
+    book.apply_logs(&lines(TAKE_PARTIAL));
+    let offer = book.get(&make.escrow).unwrap();
+    assert_eq!((offer.deposit_amount, offer.receive_amount), (750_000, 1_500_000));
+
+    book.apply_logs(&lines(UPDATE_OFFER));
+    let offer = book.get(&make.escrow).unwrap();
+    assert_eq!((offer.receive_amount, offer.expires_at), (1_200_000, EXPIRES_AT + 3600));
+
+    book.apply_logs(&lines(TAKE_FINAL));
+    assert!(book.is_empty());
+    assert_eq!(book.pairs().count(), 0);
+}
+
+#[test]
+fn book_models_basket_legs() {
+    let make = created(MAKE_BASKET);
+    let mut book = OfferBook::new();
+    book.apply_logs(&lines(MAKE_BASKET));
+
+    let offer = book.get(&make.escrow).unwrap();
+    assert!(offer.is_basket());
+    assert_eq!(offer.offered.iter().map(|leg| leg.amount).collect::<Vec<_>>(), [500_000]);
+    assert_eq!(offer.requested.iter().map(|leg| leg.amount).collect::<Vec<_>>(), [700_000]);
+
+    book.apply_logs(&lines(REFUND_BASKET));
+    assert!(book.get(&make.escrow).is_none());
+}
+
+#[test]
+fn prune_expired_follows_the_program() {
+    let mut book = OfferBook::new();
+    book.apply_logs(&lines(MAKE));
+    book.prune_expired(EXPIRES_AT);
+    assert_eq!(book.len(), 1);
+    book.prune_expired(EXPIRES_AT + 1);
+    assert!(book.is_empty());
+}
+
+fn state(mint_a: Pubkey, mint_b: Pubkey, deposit_amount: u64, receive_amount: u64) -> EscrowState {
+    let maker = Pubkey::new_unique();
+    EscrowState {
+        seed: 1,
+        maker,
+        recipient: maker,
+        mint_a,
+        mint_b,
+        receive_amount,
+        deposit_amount,
+        expires_at: EXPIRES_AT,
+        taker: None,
+        offered: vec![],
+        requested: vec![],
+        vesting: None,
+        auction: None,
+        arbitration: None,
+        milestones: None,
+        sol_vault_bump: 0,
+        bump: 255,
+    }
+}
+
+fn snapshot(state: &EscrowState) -> Vec<u8> {
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn snapshots_are_ranked_cheapest_first() {
+    let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (cheap, dear, auction) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let mut book = OfferBook::new();
+    book.apply_account(dear, &snapshot(&state(mint_a, mint_b, 100, 300)));
+    book.apply_account(cheap, &snapshot(&state(mint_a, mint_b, 100, 200)));
+
+    // an auction ends before `expires_at`, and expires with it
+    let mut auctioned = state(mint_a, mint_b, 100, 250);
+    auctioned.auction = Some(DutchAuction { end_receive_amount: 150, start_time: 0, end_time: EXPIRES_AT - 10 });
+    book.apply_account(auction, &snapshot(&auctioned));
+
+    let ranked: Vec<Pubkey> = book.offers(&mint_a, &mint_b).iter().map(|offer| offer.escrow).collect();
+    assert_eq!(ranked, [cheap, auction, dear]);
+    assert_eq!(book.get(&auction).unwrap().receive_amount_at(EXPIRES_AT), 150);
+
+    book.prune_expired(EXPIRES_AT - 5);
+    assert!(book.get(&auction).is_none());
+    // a closed account drops the offer
+    book.apply_account(dear, &[]);
+    assert_eq!(book.offers(&mint_a, &mint_b).len(), 1);
+}
+
+#[test]
+fn snapshots_keep_basket_legs() {
+    let escrow = Pubkey::new_unique();
+    let mut basket = state(Pubkey::new_unique(), Pubkey::new_unique(), 100, 200);
+    basket.requested = vec![BasketLeg { mint: Pubkey::new_unique(), amount: 7 }];
+    let mut book = OfferBook::new();
+    book.apply_account(escrow, &snapshot(&basket));
+
+    let offer = book.get(&escrow).unwrap();
+    assert!(offer.is_basket() && offer.offered.is_empty());
+    assert_eq!(offer.requested[0].amount, 7);
+}
@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Offer has not expired yet")]
+    OfferNotExpired,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Deposit and receive amounts must be greater than zero")]
+    InvalidAmount,
+    #[msg("Vault holds less than the recorded deposit")]
+    VaultBalanceMismatch,
+    #[msg("Fill amount must be between 1 and the remaining deposit")]
+    InvalidFillAmount,
+    #[msg("Offer is reserved for a different taker")]
+    InvalidTaker,
+    #[msg("Too many basket legs")]
+    TooManyLegs,
+    #[msg("Basket legs must use distinct SPL mints and non-zero amounts")]
+    InvalidLeg,
+    #[msg("Remaining accounts do not match the basket legs")]
+    InvalidLegAccount,
+    #[msg("This offer must be filled in full")]
+    PartialFillNotAllowed,
+    #[msg("Offer terms changed since they were read")]
+    TermsChanged,
+    #[msg("Fill costs more than the taker's maximum")]
+    SlippageExceeded,
+    #[msg("Fee can be at most 10,000 basis points")]
+    InvalidFee,
+    #[msg("An account this offer needs was not provided")]
+    MissingAccount,
+    #[msg("Vesting needs an SPL mint A, a positive duration and a cliff within it")]
+    InvalidVesting,
+    #[msg("Offer has already been taken")]
+    OfferTaken,
+    #[msg("Nothing has vested yet")]
+    NothingToClaim,
+    #[msg("Mint has a Token-2022 extension the escrow does not support")]
+    UnsupportedMint,
+    #[msg("Auction needs a window ending in the future and an end price between zero and the start price")]
+    InvalidAuction,
+    #[msg("Arbitration needs an SPL mint A and no basket, vesting or auction, with distinct parties")]
+    InvalidArbitration,
+    #[msg("Only the maker or the beneficiary can do this")]
+    NotAParty,
+    #[msg("Escrow is not in dispute")]
+    NotDisputed,
+    #[msg("Milestones need an SPL mint A and no basket, vesting, auction or arbitration, with amounts adding up to the deposit")]
+    InvalidMilestones,
+    #[msg("Only the milestone's approver can approve it")]
+    NotApprover,
+    #[msg("The next milestone has not been approved")]
+    MilestoneNotApproved,
+    #[msg("Remaining accounts must be the maker's escrow, vault, mint A, recipient and recipient ATA per offer")]
+    InvalidBatchAccount,
+    #[msg("Only plain SPL offers can be refunded in bulk")]
+    UnsupportedBatchOffer,
+    #[msg("The native mint is traded as SOL, wrapped SOL token accounts are not accepted")]
+    WrappedSolNotSupported,
+    #[msg("Only the program's upgrade authority can initialize the config")]
+    NotUpgradeAuthority,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+}
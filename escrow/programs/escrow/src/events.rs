@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{BasketLeg, DutchAuction};
+
+#[event]
+pub struct OfferCreated {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+    /// Extra legs swapped along with mint A and mint B.
+    pub offered: Vec<BasketLeg>,
+    pub requested: Vec<BasketLeg>,
+    pub expires_at: i64,
+    pub taker: Option<Pubkey>,
+    pub auction: Option<DutchAuction>,
+    /// Set for arbitrated and milestone escrows, which cannot be taken.
+    pub beneficiary: Option<Pubkey>,
+    pub arbiter: Option<Pubkey>,
+}
+
+#[event]
+pub struct OfferTaken {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    /// Mint A filled by this take.
+    pub amount: u64,
+    /// Mint B paid for it, protocol fee included.
+    pub cost: u64,
+    pub remaining_deposit: u64,
+    pub remaining_receive: u64,
+}
+
+#[event]
+pub struct OfferRefunded {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub deposit_amount: u64,
+}
+
+#[event]
+pub struct OfferUpdated {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub receive_amount: u64,
+    pub deposit_amount: u64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub escrow: Pubkey,
+    pub opened_by: Pubkey,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub escrow: Pubkey,
+    pub beneficiary: Pubkey,
+    pub beneficiary_amount: u64,
+    pub maker_amount: u64,
+    /// Set when the arbiter decided the split.
+    pub arbiter: Option<Pubkey>,
+}
+
+#[event]
+pub struct MilestoneApproved {
+    pub escrow: Pubkey,
+    pub index: u8,
+    pub approver: Pubkey,
+}
+
+#[event]
+pub struct MilestoneReleased {
+    pub escrow: Pubkey,
+    pub index: u8,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+}
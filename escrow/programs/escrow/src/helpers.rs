@@ -0,0 +1,214 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::{create_idempotent, get_associated_token_address_with_program_id, Create},
+    token::spl_token::native_mint,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee::TransferFeeAmount, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        onchain::invoke_transfer_checked,
+    },
+    token_interface::{
+        close_account, harvest_withheld_tokens_to_mint, CloseAccount, HarvestWithheldTokensToMint, Mint,
+        TokenAccount,
+    },
+};
+
+use crate::errors::EscrowError;
+
+// CPI helpers shared by the instructions. The token account checks are for
+// accounts passed through `remaining_accounts`, which Anchor does not validate
+// for us.
+
+/// Token-2022 extensions an offer's mints cannot have: non-transferable tokens
+/// could never leave the vault, and a permanent delegate could drain it.
+pub const REJECTED_EXTENSIONS: &[ExtensionType] = &[ExtensionType::NonTransferable, ExtensionType::PermanentDelegate];
+
+/// Basket legs move fixed amounts with no extra accounts, so on top of the
+/// above their mints cannot charge transfer fees or run transfer hooks.
+pub const REJECTED_LEG_EXTENSIONS: &[ExtensionType] = &[
+    ExtensionType::NonTransferable,
+    ExtensionType::PermanentDelegate,
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::TransferHook,
+];
+
+/// Milestones pay out fixed tranches of the deposit, so mint A cannot charge
+/// transfer fees that would leave the vault short of them.
+pub const REJECTED_MILESTONE_EXTENSIONS: &[ExtensionType] = &[
+    ExtensionType::NonTransferable,
+    ExtensionType::PermanentDelegate,
+    ExtensionType::TransferFeeConfig,
+];
+
+/// Offers use the native mint to mean lamports moved through the system
+/// program, so wrapped SOL cannot be traded as a token. Instructions refuse
+/// token accounts passed for a native side with `WrappedSolNotSupported`
+/// instead of ignoring them.
+pub fn is_native(mint: &Pubkey) -> bool {
+    *mint == native_mint::ID
+}
+
+/// The SOL vault is a plain system account, so on top of the deposit it has to
+/// hold its own rent-exempt minimum until it is emptied.
+pub fn sol_vault_rent() -> Result<u64> {
+    Ok(Rent::get()?.minimum_balance(0))
+}
+
+pub fn transfer_lamports<'info>(
+    system_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = Transfer {
+        from: from.clone(),
+        to: to.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+    transfer(cpi_ctx, amount)
+}
+
+pub fn mint_decimals(mint: &AccountInfo, token_program: &Pubkey) -> Result<u8> {
+    require_keys_eq!(*mint.owner, *token_program, EscrowError::InvalidLegAccount);
+    let mint = Mint::try_deserialize(&mut &mint.data.borrow()[..])?;
+    Ok(mint.decimals)
+}
+
+/// Rejects Token-2022 mints carrying any of the `rejected` extensions. Legacy
+/// SPL Token mints have no extensions.
+pub fn check_mint_extensions(mint: &AccountInfo, rejected: &[ExtensionType]) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let extensions = mint.get_extension_types()?;
+    require!(
+        !extensions.iter().any(|extension| rejected.contains(extension)),
+        EscrowError::UnsupportedMint
+    );
+    Ok(())
+}
+
+/// Splits `remaining_accounts` into the `legs` basket accounts and whatever
+/// follows them, the extra accounts transfer hooks on mint A or B need.
+pub fn split_hook_accounts<'a, 'info>(
+    remaining: &'a [AccountInfo<'info>],
+    legs: usize,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    require!(remaining.len() >= legs, EscrowError::InvalidLegAccount);
+    Ok(remaining.split_at(legs))
+}
+
+pub fn token_balance(account: &AccountInfo) -> Result<u64> {
+    let account = TokenAccount::try_deserialize(&mut &account.data.borrow()[..])?;
+    Ok(account.amount)
+}
+
+pub fn check_ata(ata: &AccountInfo, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Result<()> {
+    require_keys_eq!(
+        *ata.key,
+        get_associated_token_address_with_program_id(owner, mint, token_program),
+        EscrowError::InvalidLegAccount
+    );
+    Ok(())
+}
+
+pub fn create_ata<'info>(
+    payer: &AccountInfo<'info>,
+    ata: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let cpi_accounts = Create {
+        payer: payer.clone(),
+        associated_token: ata.clone(),
+        authority: owner.clone(),
+        mint: mint.clone(),
+        system_program: system_program.clone(),
+        token_program: token_program.clone(),
+    };
+    create_idempotent(CpiContext::new(associated_token_program.clone(), cpi_accounts))
+}
+
+/// `transfer_checked` that also works for transfer-hook mints: the hook's
+/// extra accounts are picked out of `hook_accounts`.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    decimals: u8,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_transfer_checked(
+        token_program.key,
+        from.clone(),
+        mint.clone(),
+        to.clone(),
+        authority.clone(),
+        hook_accounts,
+        amount,
+        decimals,
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Moves the transfer fees withheld in `account` to the mint, Token-2022
+/// refuses to close an account still holding some. Harvesting needs no
+/// authority.
+pub fn harvest_withheld<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+) -> Result<()> {
+    if *token_program.key != spl_token_2022::ID {
+        return Ok(());
+    }
+    let withheld = {
+        let data = account.try_borrow_data()?;
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+        state
+            .get_extension::<TransferFeeAmount>()
+            .map_or(0, |fee| u64::from(fee.withheld_amount))
+    };
+    if withheld == 0 {
+        return Ok(());
+    }
+    let cpi_accounts = HarvestWithheldTokensToMint {
+        token_program_id: token_program.clone(),
+        mint: mint.clone(),
+    };
+    harvest_withheld_tokens_to_mint(CpiContext::new(token_program.clone(), cpi_accounts), vec![account.clone()])
+}
+
+/// Closes `account` to `destination`. A Token-2022 account still holding
+/// withheld transfer fees cannot close, so vaults go through `harvest_withheld`
+/// first, which is why the instructions closing one take mint A writable.
+pub fn close_token_account<'info>(
+    token_program: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = CloseAccount {
+        account: account.clone(),
+        destination: destination.clone(),
+        authority: authority.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds);
+    close_account(cpi_ctx)
+}
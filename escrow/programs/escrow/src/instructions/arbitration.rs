@@ -0,0 +1,177 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    errors::EscrowError,
+    events::{DisputeOpened, EscrowReleased},
+    helpers::{close_token_account, harvest_withheld, transfer_tokens},
+    state::EscrowState,
+};
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    pub party: Signer<'info>,
+    #[account(
+        mut,
+        constraint = escrow.is_party(party.key) @ EscrowError::NotAParty,
+        constraint = !escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferExpired,
+        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, EscrowState>,
+}
+
+impl<'info> OpenDispute<'info> {
+    pub fn open_dispute(&mut self) -> Result<()> {
+        let arbitration = self.escrow.arbitration.as_mut().ok_or(EscrowError::InvalidArbitration)?;
+        arbitration.disputed = true;
+
+        emit!(DisputeOpened {
+            escrow: self.escrow.key(),
+            opened_by: self.party.key(),
+        });
+        Ok(())
+    }
+}
+
+/// Pays out an arbitrated escrow, either on the approval of both parties or on
+/// the arbiter's decision. `authority` is the maker, the beneficiary or the
+/// arbiter depending on the instruction.
+#[derive(Accounts)]
+pub struct Settle<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    /// Receives the maker's share and the rent. Its token account must
+    /// already exist; the parties settling do not pay for it.
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint_a,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program,
+    )]
+    pub beneficiary_mint_a_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub recipient_mint_a_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = recipient,
+        has_one = mint_a,
+        constraint = escrow.arbitration.as_ref().is_some_and(|a| a.beneficiary == beneficiary.key())
+            @ EscrowError::InvalidArbitration,
+        constraint = !escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferExpired,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Box<Account<'info, EscrowState>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Settle<'info> {
+    /// Records the authority's approval. Returns whether both parties have
+    /// now approved the release.
+    pub fn approve(&mut self) -> Result<bool> {
+        let authority = self.authority.key();
+        let maker = self.maker.key();
+        let arbitration = self.escrow.arbitration.as_mut().ok_or(EscrowError::InvalidArbitration)?;
+        if authority == maker {
+            arbitration.maker_approved = true;
+        } else if authority == arbitration.beneficiary {
+            arbitration.beneficiary_approved = true;
+        } else {
+            return err!(EscrowError::NotAParty);
+        }
+        Ok(arbitration.maker_approved && arbitration.beneficiary_approved)
+    }
+
+    /// Only the arbiter resolves, only a disputed escrow, and only with what
+    /// the vault holds.
+    pub fn check_resolution(&self, beneficiary_amount: u64) -> Result<()> {
+        let arbitration = self.escrow.arbitration.as_ref().ok_or(EscrowError::InvalidArbitration)?;
+        require_keys_eq!(self.authority.key(), arbitration.arbiter, EscrowError::InvalidArbitration);
+        require!(arbitration.disputed, EscrowError::NotDisputed);
+        require!(beneficiary_amount <= self.vault.amount, EscrowError::InvalidAmount);
+        Ok(())
+    }
+
+    /// Sends `beneficiary_amount` to the beneficiary and the rest of the vault
+    /// to the maker's recipient, then closes the vault and escrow to it.
+    pub fn settle(&mut self, beneficiary_amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let maker_amount = self.vault.amount - beneficiary_amount;
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+
+        for (to, amount) in [
+            (self.beneficiary_mint_a_ata.to_account_info(), beneficiary_amount),
+            (self.recipient_mint_a_ata.to_account_info(), maker_amount),
+        ] {
+            if amount > 0 {
+                transfer_tokens(
+                    &self.token_program.to_account_info(),
+                    &self.vault.to_account_info(),
+                    &to,
+                    &self.mint_a.to_account_info(),
+                    &self.escrow.to_account_info(),
+                    hook_accounts,
+                    amount,
+                    self.mint_a.decimals,
+                    seeds,
+                )?;
+            }
+        }
+
+        harvest_withheld(
+            &self.token_program.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+        close_token_account(
+            &self.token_program.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.recipient.to_account_info(),
+            &self.escrow.to_account_info(),
+            seeds,
+        )?;
+
+        let arbitration = self.escrow.arbitration.as_ref().ok_or(EscrowError::InvalidArbitration)?;
+        let decided_by_arbiter = self.authority.key() == arbitration.arbiter;
+        emit!(EscrowReleased {
+            escrow: self.escrow.key(),
+            beneficiary: self.beneficiary.key(),
+            beneficiary_amount,
+            maker_amount,
+            arbiter: decided_by_arbiter.then_some(arbitration.arbiter),
+        });
+        self.escrow.close(self.recipient.to_account_info())
+    }
+}
@@ -0,0 +1,142 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    errors::EscrowError,
+    helpers::{close_token_account, harvest_withheld, transfer_tokens},
+    state::EscrowState,
+};
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    /// Gets back anything sent to the vault on top of the vested total.
+    pub recipient: SystemAccount<'info>,
+    #[account(mut)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = mint_a,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program,
+    )]
+    pub beneficiary_mint_a_ata: InterfaceAccount<'info, TokenAccount>,
+    /// Only needed for the final claim when the vault holds more than the
+    /// vested total.
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = mint_a,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub recipient_mint_a_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = recipient,
+        has_one = mint_a,
+        constraint = escrow.is_vesting() @ EscrowError::NothingToClaim,
+        constraint = escrow.vesting.as_ref().is_some_and(|v| v.beneficiary == beneficiary.key()) @ EscrowError::InvalidTaker,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, EscrowState>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClaimVested<'info> {
+    /// `hook_accounts` are the extra accounts a transfer hook on mint A needs.
+    pub fn claim(&mut self, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vesting = self.escrow.vesting.as_mut().ok_or(EscrowError::NothingToClaim)?;
+        let amount = vesting.vested(now) - vesting.claimed;
+        require!(amount > 0, EscrowError::NothingToClaim);
+        vesting.claimed += amount;
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.beneficiary_mint_a_ata.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.escrow.to_account_info(),
+            hook_accounts,
+            amount,
+            self.mint_a.decimals,
+            seeds,
+        )
+    }
+
+    /// Once everything is claimed the vault and escrow close to the beneficiary,
+    /// like a regular take. Tokens sent to the vault on top of the vested total
+    /// would keep it from closing, so they go back to the maker's recipient
+    /// first.
+    pub fn close_if_done(&mut self, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let done = self.escrow.vesting.as_ref().is_some_and(|v| v.claimed == v.total);
+        if !done {
+            return Ok(());
+        }
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        self.vault.reload()?;
+        let excess = self.vault.amount;
+        if excess > 0 {
+            let recipient_mint_a_ata = self.recipient_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                &self.vault.to_account_info(),
+                &recipient_mint_a_ata.to_account_info(),
+                &self.mint_a.to_account_info(),
+                &self.escrow.to_account_info(),
+                hook_accounts,
+                excess,
+                self.mint_a.decimals,
+                seeds,
+            )?;
+        }
+
+        harvest_withheld(
+            &self.token_program.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+        close_token_account(
+            &self.token_program.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.beneficiary.to_account_info(),
+            &self.escrow.to_account_info(),
+            seeds,
+        )?;
+        self.escrow.close(self.beneficiary.to_account_info())
+    }
+}
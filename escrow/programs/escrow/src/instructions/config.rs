@@ -0,0 +1,65 @@
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+use crate::{errors::EscrowError, state::EscrowConfig};
+
+/// Creates the fee config. It has to run once after the program is deployed,
+/// since `take` needs the config and fails until it exists. Only the program's
+/// upgrade authority can create it; it becomes the config's admin.
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ EscrowError::NotUpgradeAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        bump,
+        space = 8 + EscrowConfig::INIT_SPACE,
+    )]
+    pub config: Account<'info, EscrowConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConfig<'info> {
+    pub fn init_config(&mut self, fee_bps: u16, treasury: Pubkey, bumps: &InitializeConfigBumps) -> Result<()> {
+        require!(fee_bps <= 10_000, EscrowError::InvalidFee);
+
+        self.config.set_inner(EscrowConfig {
+            admin: self.admin.key(),
+            fee_bps,
+            treasury,
+            bump: bumps.config,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = admin,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, EscrowConfig>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    pub fn update_config(&mut self, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        require!(fee_bps <= 10_000, EscrowError::InvalidFee);
+
+        self.config.fee_bps = fee_bps;
+        self.config.treasury = treasury;
+        Ok(())
+    }
+}
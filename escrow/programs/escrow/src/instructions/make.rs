@@ -1,10 +1,23 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{ Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface },
+    token_interface::{ Mint, TokenAccount, TokenInterface },
+};
+use crate::{
+    errors::EscrowError,
+    events::OfferCreated,
+    helpers::{
+        check_ata, check_mint_extensions, create_ata, is_native, mint_decimals, sol_vault_rent, transfer_lamports,
+        transfer_tokens, REJECTED_EXTENSIONS, REJECTED_LEG_EXTENSIONS, REJECTED_MILESTONE_EXTENSIONS,
+    },
+    state::{Arbitration, BasketLeg, EscrowState, Milestone, Milestones, OfferTerms, Vesting},
 };
-use crate::state::EscrowState;
 
+/// Opens an offer. Mints can be SPL Token or Token-2022, except Token-2022
+/// mints with the extensions in `REJECTED_EXTENSIONS`. `remaining_accounts`
+/// holds the basket leg accounts followed by the extra accounts a transfer
+/// hook on mint A needs, if any; `take`, `refund`, `update_offer` and
+/// `claim_vested` take hook accounts the same way.
 #[derive(Accounts)]
 #[instruction(seed:u64)]
 pub struct Make<'info> {
@@ -16,8 +29,9 @@ pub struct Make<'info> {
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
+        associated_token::token_program = token_program,
     )]
-    pub maker_mint_a_ata: InterfaceAccount<'info, TokenAccount>,
+    pub maker_mint_a_ata: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
         payer = maker,
@@ -30,38 +44,212 @@ pub struct Make<'info> {
         init,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
         payer = maker,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Holds the deposit instead of `vault` when mint A is native SOL.
+    #[account(
+        mut,
+        seeds = [b"sol_vault", escrow.key().as_ref()],
+        bump,
+    )]
+    pub sol_vault: Option<SystemAccount<'info>>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
 }
 
 impl<'info> Make<'info> {
-    pub fn make(&mut self, seed: u64, receive_amount: u64, bumps: MakeBumps,) -> Result<()> {
+    pub fn make(&mut self, seed: u64, receive_amount: u64, deposit_amount: u64, expires_at: i64, terms: OfferTerms, bumps: MakeBumps,) -> Result<()> {
+        let OfferTerms { taker, offered, requested, vesting, auction, arbitration, milestones, recipient } = terms;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            !is_native(&self.mint_a.key()) || (self.maker_mint_a_ata.is_none() && self.vault.is_none()),
+            EscrowError::WrappedSolNotSupported
+        );
+        require!(deposit_amount > 0 && receive_amount > 0, EscrowError::InvalidAmount);
+        require!(expires_at > now, EscrowError::InvalidExpiry);
+        if let Some(auction) = &auction {
+            auction.validate(receive_amount, now)?;
+        }
+        if let Some(terms) = &arbitration {
+            require!(
+                !is_native(&self.mint_a.key())
+                    && offered.is_empty()
+                    && requested.is_empty()
+                    && vesting.is_none()
+                    && auction.is_none()
+                    && terms.beneficiary != self.maker.key()
+                    && terms.arbiter != self.maker.key()
+                    && terms.arbiter != terms.beneficiary,
+                EscrowError::InvalidArbitration
+            );
+        }
+        if let Some(terms) = &milestones {
+            terms.validate(deposit_amount)?;
+            require!(
+                !is_native(&self.mint_a.key())
+                    && offered.is_empty()
+                    && requested.is_empty()
+                    && vesting.is_none()
+                    && auction.is_none()
+                    && arbitration.is_none()
+                    && terms.beneficiary != self.maker.key(),
+                EscrowError::InvalidMilestones
+            );
+        }
+        let rejected_a = if milestones.is_some() { REJECTED_MILESTONE_EXTENSIONS } else { REJECTED_EXTENSIONS };
+        check_mint_extensions(&self.mint_a.to_account_info(), rejected_a)?;
+        check_mint_extensions(&self.mint_b.to_account_info(), REJECTED_EXTENSIONS)?;
+        BasketLeg::validate(&self.mint_a.key(), &offered)?;
+        BasketLeg::validate(&self.mint_b.key(), &requested)?;
+        if let Some(terms) = &vesting {
+            require!(
+                terms.duration > 0
+                    && (0..=terms.duration).contains(&terms.cliff)
+                    && !is_native(&self.mint_a.key()),
+                EscrowError::InvalidVesting
+            );
+        }
+
         self.escrow.set_inner(EscrowState {
             receive_amount,
+            deposit_amount,
             seed,
             maker: *self.maker.key,
+            recipient: recipient.unwrap_or(*self.maker.key),
             mint_a: self.mint_a.key(),
             mint_b: self.mint_b.key(),
+            expires_at,
+            taker,
+            offered,
+            requested,
+            vesting: vesting.map(|terms| Vesting {
+                cliff: terms.cliff,
+                duration: terms.duration,
+                start: 0,
+                beneficiary: Pubkey::default(),
+                total: 0,
+                claimed: 0,
+            }),
+            auction,
+            arbitration: arbitration.map(|terms| Arbitration {
+                beneficiary: terms.beneficiary,
+                arbiter: terms.arbiter,
+                maker_approved: false,
+                beneficiary_approved: false,
+                disputed: false,
+            }),
+            milestones: milestones.map(|terms| Milestones {
+                beneficiary: terms.beneficiary,
+                milestones: terms
+                    .tranches
+                    .into_iter()
+                    .map(|t| Milestone { amount: t.amount, approver: t.approver, approved: false })
+                    .collect(),
+                released: 0,
+            }),
+            sol_vault_bump: bumps.sol_vault.unwrap_or_default(),
             bump: bumps.escrow,
         });
         Ok(())
     }
 
-    pub fn deposit(&mut self, deposit_amount: u64) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_accounts = TransferChecked {
-            from: self.maker_mint_a_ata.to_account_info(),
-            to: self.vault.to_account_info(),   
-            authority: self.maker.to_account_info(),
-            mint: self.mint_a.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer_checked(cpi_ctx, deposit_amount, self.mint_a.decimals)?;
+    /// Locks the deposit. Transfer fees are withheld on the way in, so for
+    /// such mints the offer records what actually reached the vault.
+    pub fn deposit(&mut self, deposit_amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.native_a() {
+            let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            let amount = deposit_amount
+                .checked_add(sol_vault_rent()?)
+                .ok_or(EscrowError::Overflow)?;
+            return transfer_lamports(
+                &self.system_program.to_account_info(),
+                &self.maker.to_account_info(),
+                &sol_vault.to_account_info(),
+                amount,
+                &[],
+            );
+        }
+
+        let maker_mint_a_ata = self.maker_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+        let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &maker_mint_a_ata.to_account_info(),
+            &vault.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.maker.to_account_info(),
+            hook_accounts,
+            deposit_amount,
+            self.mint_a.decimals,
+            &[],
+        )?;
+
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingAccount)?;
+        vault.reload()?;
+        require!(vault.amount > 0, EscrowError::InvalidAmount);
+        self.escrow.deposit_amount = vault.amount;
+        Ok(())
+    }
+
+    pub fn emit_created(&self) {
+        emit!(OfferCreated {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            seed: self.escrow.seed,
+            mint_a: self.escrow.mint_a,
+            mint_b: self.escrow.mint_b,
+            deposit_amount: self.escrow.deposit_amount,
+            receive_amount: self.escrow.receive_amount,
+            offered: self.escrow.offered.clone(),
+            requested: self.escrow.requested.clone(),
+            expires_at: self.escrow.expires_at,
+            taker: self.escrow.taker,
+            auction: self.escrow.auction.clone(),
+            beneficiary: self.escrow.beneficiary(),
+            arbiter: self.escrow.arbitration.as_ref().map(|a| a.arbiter),
+        });
+    }
+
+    /// Locks every extra offered leg in its own escrow-owned vault. Expects
+    /// `[mint, maker ata, vault]` per leg in `remaining_accounts`.
+    pub fn deposit_basket(&mut self, remaining: &[AccountInfo<'info>]) -> Result<()> {
+        let legs = &self.escrow.offered;
+        require!(remaining.len() == legs.len() * 3, EscrowError::InvalidLegAccount);
+
+        let token_program = self.token_program.key();
+        for (leg, accounts) in legs.iter().zip(remaining.chunks_exact(3)) {
+            let (mint, maker_ata, vault) = (&accounts[0], &accounts[1], &accounts[2]);
+            require_keys_eq!(leg.mint, *mint.key, EscrowError::InvalidLegAccount);
+            check_mint_extensions(mint, REJECTED_LEG_EXTENSIONS)?;
+            check_ata(maker_ata, self.maker.key, mint.key, &token_program)?;
+            check_ata(vault, &self.escrow.key(), mint.key, &token_program)?;
+            let decimals = mint_decimals(mint, &token_program)?;
+
+            create_ata(
+                &self.maker.to_account_info(),
+                vault,
+                &self.escrow.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                &self.token_program.to_account_info(),
+                &self.associated_token_program.to_account_info(),
+            )?;
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                maker_ata,
+                vault,
+                mint,
+                &self.maker.to_account_info(),
+                &[],
+                leg.amount,
+                decimals,
+                &[],
+            )?;
+        }
         Ok(())
     }
-        
 }
\ No newline at end of file
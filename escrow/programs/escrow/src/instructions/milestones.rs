@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    errors::EscrowError,
+    events::{MilestoneApproved, MilestoneReleased},
+    helpers::{close_token_account, harvest_withheld, transfer_tokens},
+    state::EscrowState,
+};
+
+#[derive(Accounts)]
+pub struct ApproveMilestone<'info> {
+    pub approver: Signer<'info>,
+    #[account(
+        mut,
+        constraint = escrow.milestones.is_some() @ EscrowError::InvalidMilestones,
+        constraint = !escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferExpired,
+        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, EscrowState>,
+}
+
+impl<'info> ApproveMilestone<'info> {
+    /// Milestones can be approved ahead of time; they are still paid in order.
+    pub fn approve(&mut self, index: u8) -> Result<()> {
+        let milestones = self.escrow.milestones.as_mut().ok_or(EscrowError::InvalidMilestones)?;
+        require!(index >= milestones.released, EscrowError::InvalidMilestones);
+        let milestone = milestones
+            .milestones
+            .get_mut(index as usize)
+            .ok_or(EscrowError::InvalidMilestones)?;
+        require_keys_eq!(self.approver.key(), milestone.approver, EscrowError::NotApprover);
+        milestone.approved = true;
+
+        emit!(MilestoneApproved {
+            escrow: self.escrow.key(),
+            index,
+            approver: self.approver.key(),
+        });
+        Ok(())
+    }
+}
+
+/// Pays the next milestone to the beneficiary. Anyone can crank it once the
+/// milestone is approved; `payer` covers the beneficiary's token account if
+/// it does not exist yet.
+#[derive(Accounts)]
+pub struct ReleaseMilestone<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    /// Receives the rent once the last milestone is paid.
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_a,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program,
+    )]
+    pub beneficiary_mint_a_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = recipient,
+        has_one = mint_a,
+        constraint = escrow.milestones.as_ref().is_some_and(|m| m.beneficiary == beneficiary.key())
+            @ EscrowError::InvalidMilestones,
+        constraint = !escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferExpired,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Box<Account<'info, EscrowState>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ReleaseMilestone<'info> {
+    /// Pays out the next milestone. The last one takes whatever is left in the
+    /// vault. Returns whether every milestone is now paid.
+    pub fn release(&mut self, hook_accounts: &[AccountInfo<'info>]) -> Result<bool> {
+        let vault_amount = self.vault.amount;
+        let milestones = self.escrow.milestones.as_mut().ok_or(EscrowError::InvalidMilestones)?;
+        let index = milestones.released;
+        let milestone = milestones
+            .milestones
+            .get(index as usize)
+            .ok_or(EscrowError::InvalidMilestones)?;
+        require!(milestone.approved, EscrowError::MilestoneNotApproved);
+        let done = milestones.is_last();
+        let amount = if done { vault_amount } else { milestone.amount };
+        require!(amount <= vault_amount, EscrowError::VaultBalanceMismatch);
+        milestones.released += 1;
+        self.escrow.deposit_amount = vault_amount - amount;
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.beneficiary_mint_a_ata.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.escrow.to_account_info(),
+            hook_accounts,
+            amount,
+            self.mint_a.decimals,
+            seeds,
+        )?;
+
+        emit!(MilestoneReleased {
+            escrow: self.escrow.key(),
+            index,
+            beneficiary: self.beneficiary.key(),
+            amount,
+        });
+        Ok(done)
+    }
+
+    /// After the last milestone the vault and escrow close to the maker's
+    /// recipient.
+    pub fn close(&mut self) -> Result<()> {
+        harvest_withheld(
+            &self.token_program.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        close_token_account(
+            &self.token_program.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.recipient.to_account_info(),
+            &self.escrow.to_account_info(),
+            seeds,
+        )?;
+        self.escrow.close(self.recipient.to_account_info())
+    }
+}
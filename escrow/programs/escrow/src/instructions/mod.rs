@@ -1,6 +1,18 @@
+mod arbitration;
+mod claim_vested;
+mod config;
 mod make;
+mod milestones;
 mod take;
 mod refund;
+mod refund_many;
+mod update_offer;
+pub use arbitration::*;
+pub use claim_vested::*;
+pub use config::*;
 pub use make::*;
+pub use milestones::*;
 pub use take::*;
-pub use refund::*;
\ No newline at end of file
+pub use refund::*;
+pub use refund_many::*;
+pub use update_offer::*;
\ No newline at end of file
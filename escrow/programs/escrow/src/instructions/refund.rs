@@ -1,51 +1,128 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked}};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface}};
 
 
-use crate::state::EscrowState;
+use crate::{
+    errors::EscrowError,
+    events::OfferRefunded,
+    helpers::{check_ata, close_token_account, create_ata, harvest_withheld, mint_decimals, token_balance, transfer_lamports, transfer_tokens},
+    state::EscrowState,
+};
 
+/// Refunds the maker. The maker can refund at any time; once the offer has
+/// expired anyone can crank the refund, tokens and rent still go to the
+/// maker's recipient. Arbitrated and milestone escrows only refund once
+/// expired.
 #[derive(Accounts)]
-
 pub struct Refund<'info> {
     #[account(mut)]
-    pub maker: Signer<'info>,
+    pub caller: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    #[account(mut)]
     pub mint_a: InterfaceAccount<'info, Mint>,
     pub mint_b: InterfaceAccount<'info, Mint>,
     #[account(
-        mut,
+        init_if_needed,
+        payer = caller,
         associated_token::mint = mint_a,
-        associated_token::authority = maker,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
     )]
-    pub maker_mint_a_ata: InterfaceAccount<'info, TokenAccount>,
+    pub recipient_mint_a_ata: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
+        has_one = maker,
+        has_one = recipient,
+        has_one = mint_a,
+        constraint = !escrow.is_vesting() @ EscrowError::OfferTaken,
+        constraint = (caller.key() == maker.key() && !escrow.has_beneficiary())
+            || escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferNotExpired,
         seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
-        close = maker,
+        close = recipient,
     )]
     pub escrow: Account<'info, EscrowState>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"sol_vault", escrow.key().as_ref()],
+        bump = escrow.sol_vault_bump,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub sol_vault: Option<SystemAccount<'info>>,
     pub system_program: Program<'info, System>,
    pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
 }
 
 impl<'info> Refund<'info> {
-    pub fn refund(&mut self,) -> Result<()> {
-       let cpi_program = self.token_program.to_account_info();
-       let cpi_accounts = TransferChecked {
-           from: self.vault.to_account_info(),
-           to: self.maker_mint_a_ata.to_account_info(),
-           authority: self.escrow.to_account_info(),
-           mint: self.mint_a.to_account_info(),    
-    };
+    pub fn refund(&mut self, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+       if self.escrow.native_a() {
+           require!(
+               self.recipient_mint_a_ata.is_none() && self.vault.is_none(),
+               EscrowError::WrappedSolNotSupported
+           );
+           // the deposit and the SOL vault's rent both go back to the recipient
+           let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+           let escrow_key = self.escrow.key();
+           let seeds: &[&[&[u8]]] = &[&[b"sol_vault", escrow_key.as_ref(), &[self.escrow.sol_vault_bump]]];
+           return transfer_lamports(
+               &self.system_program.to_account_info(),
+               &sol_vault.to_account_info(),
+               &self.recipient.to_account_info(),
+               sol_vault.lamports(),
+               seeds,
+           );
+       }
+
+       let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+       let recipient_mint_a_ata = self.recipient_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+       let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &vault.to_account_info(),
+            &recipient_mint_a_ata.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.escrow.to_account_info(),
+            hook_accounts,
+            vault.amount,
+            self.mint_a.decimals,
+            seeds,
+        )
+    }
+
+    pub fn emit_refunded(&self) {
+        emit!(OfferRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            deposit_amount: self.escrow.deposit_amount,
+        });
+    }
+
+    /// Returns every extra offered leg. Expects `[mint, vault, recipient ata]`
+    /// per leg in `remaining_accounts`.
+    pub fn refund_basket(&mut self, remaining: &[AccountInfo<'info>]) -> Result<()> {
+        let legs = &self.escrow.offered;
+        require!(remaining.len() == legs.len() * 3, EscrowError::InvalidLegAccount);
 
-    let seeds: &[&[&[u8]]] = &[
+        let token_program = self.token_program.key();
+        let escrow_key = self.escrow.key();
+        let seeds: &[&[&[u8]]] = &[
             &[
                 b"escrow",
                 self.maker.key.as_ref(),
@@ -53,18 +130,57 @@ impl<'info> Refund<'info> {
                 &[self.escrow.bump],
             ],
         ];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
-        transfer_checked(cpi_ctx, self.escrow.receive_amount, self.mint_a.decimals)?;
+
+        for (leg, accounts) in legs.iter().zip(remaining.chunks_exact(3)) {
+            let (mint, vault, recipient_ata) = (&accounts[0], &accounts[1], &accounts[2]);
+            require_keys_eq!(leg.mint, *mint.key, EscrowError::InvalidLegAccount);
+            check_ata(vault, &escrow_key, mint.key, &token_program)?;
+            check_ata(recipient_ata, self.recipient.key, mint.key, &token_program)?;
+            let decimals = mint_decimals(mint, &token_program)?;
+
+            create_ata(
+                &self.caller.to_account_info(),
+                recipient_ata,
+                &self.recipient.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                &self.token_program.to_account_info(),
+                &self.associated_token_program.to_account_info(),
+            )?;
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                vault,
+                recipient_ata,
+                mint,
+                &self.escrow.to_account_info(),
+                &[],
+                token_balance(vault)?,
+                decimals,
+                seeds,
+            )?;
+            close_token_account(
+                &self.token_program.to_account_info(),
+                vault,
+                &self.recipient.to_account_info(),
+                &self.escrow.to_account_info(),
+                seeds,
+            )?;
+        }
         Ok(())
     }
 
     pub fn close(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.maker_mint_a_ata.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
+        // an emptied SOL vault is already gone, only token vaults need closing
+        if self.escrow.native_a() {
+            return Ok(());
+        }
+        let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+        harvest_withheld(
+            &self.token_program.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &vault.to_account_info(),
+        )?;
+
         let seeds: &[&[&[u8]]] = &[
             &[
                 b"escrow",
@@ -73,8 +189,12 @@ impl<'info> Refund<'info> {
                 &[self.escrow.bump],
             ],
         ];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
-        close_account(cpi_ctx)?;
-        Ok(())
+        close_token_account(
+            &self.token_program.to_account_info(),
+            &vault.to_account_info(),
+            &self.recipient.to_account_info(),
+            &self.escrow.to_account_info(),
+            seeds,
+        )
     }
-}
\ No newline at end of file
+}
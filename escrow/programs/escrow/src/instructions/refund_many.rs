@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::TokenInterface};
+
+use crate::{
+    errors::EscrowError,
+    events::OfferRefunded,
+    helpers::{
+        check_ata, close_token_account, create_ata, harvest_withheld, mint_decimals, token_balance, transfer_tokens,
+    },
+    state::EscrowState,
+};
+
+/// Accounts per offer in `remaining_accounts`: `[escrow, vault, mint A,
+/// recipient, recipient ata]`.
+pub const REFUND_MANY_ACCOUNTS: usize = 5;
+
+/// Refunds and closes several of the maker's offers in one transaction. Only
+/// plain offers qualify: an SPL mint A without a transfer hook, no basket, no
+/// started vesting and no beneficiary. Everything else goes through `refund`.
+#[derive(Accounts)]
+pub struct RefundMany<'info> {
+    /// Pays for any recipient token account that has to be recreated.
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RefundMany<'info> {
+    pub fn refund_many(&self, remaining: &'info [AccountInfo<'info>]) -> Result<()> {
+        let offers = remaining.chunks_exact(REFUND_MANY_ACCOUNTS);
+        require!(
+            !remaining.is_empty() && offers.remainder().is_empty(),
+            EscrowError::InvalidBatchAccount
+        );
+        let mut refunded = Vec::with_capacity(remaining.len() / REFUND_MANY_ACCOUNTS);
+        for accounts in offers {
+            refunded.push(self.refund_one(accounts)?);
+        }
+        // Closing moves lamports out of the escrow directly, which the runtime
+        // only allows once no more CPIs follow.
+        for (escrow, recipient) in refunded {
+            escrow.close(recipient.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Empties and closes one offer's vault. Returns the escrow account and
+    /// the recipient it closes to.
+    fn refund_one(
+        &self,
+        accounts: &'info [AccountInfo<'info>],
+    ) -> Result<(Account<'info, EscrowState>, &'info AccountInfo<'info>)> {
+        let (escrow_info, vault, mint, recipient, recipient_ata) =
+            (&accounts[0], &accounts[1], &accounts[2], &accounts[3], &accounts[4]);
+        let escrow = Account::<EscrowState>::try_from(escrow_info)?;
+
+        // the escrow must be the maker's own PDA, not just claim them as maker
+        let seed = escrow.seed.to_le_bytes();
+        let bump = [escrow.bump];
+        let signer: &[&[u8]] = &[b"escrow", self.maker.key.as_ref(), &seed, &bump];
+        let address = Pubkey::create_program_address(signer, &crate::ID)
+            .map_err(|_| error!(EscrowError::InvalidBatchAccount))?;
+        require_keys_eq!(address, escrow.key(), EscrowError::InvalidBatchAccount);
+        require_keys_eq!(escrow.maker, self.maker.key(), EscrowError::InvalidBatchAccount);
+        require_keys_eq!(escrow.mint_a, *mint.key, EscrowError::InvalidBatchAccount);
+        require_keys_eq!(escrow.recipient, *recipient.key, EscrowError::InvalidBatchAccount);
+        require!(
+            !escrow.native_a() && !escrow.is_basket() && !escrow.is_vesting() && !escrow.has_beneficiary(),
+            EscrowError::UnsupportedBatchOffer
+        );
+
+        let token_program = self.token_program.key();
+        check_ata(vault, &escrow.key(), mint.key, &token_program)?;
+        check_ata(recipient_ata, recipient.key, mint.key, &token_program)?;
+        let decimals = mint_decimals(mint, &token_program)?;
+        let seeds = &[signer];
+
+        create_ata(
+            &self.maker.to_account_info(),
+            recipient_ata,
+            recipient,
+            mint,
+            &self.system_program.to_account_info(),
+            &self.token_program.to_account_info(),
+            &self.associated_token_program.to_account_info(),
+        )?;
+        let amount = token_balance(vault)?;
+        if amount > 0 {
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                vault,
+                recipient_ata,
+                mint,
+                escrow_info,
+                &[],
+                amount,
+                decimals,
+                seeds,
+            )?;
+        }
+        harvest_withheld(&self.token_program.to_account_info(), mint, vault)?;
+        close_token_account(&self.token_program.to_account_info(), vault, recipient, escrow_info, seeds)?;
+
+        emit!(OfferRefunded {
+            escrow: escrow.key(),
+            maker: self.maker.key(),
+            deposit_amount: escrow.deposit_amount,
+        });
+        Ok((escrow, recipient))
+    }
+}
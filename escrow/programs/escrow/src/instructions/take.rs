@@ -1,53 +1,100 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    associated_token::AssociatedToken, token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, close_account, CloseAccount }
+    associated_token::AssociatedToken, token_interface::{ Mint, TokenAccount, TokenInterface }
+};
+use crate::{
+    errors::EscrowError,
+    events::OfferTaken,
+    helpers::{
+        check_ata, check_mint_extensions, close_token_account, create_ata, harvest_withheld, mint_decimals,
+        sol_vault_rent, token_balance, transfer_lamports, transfer_tokens, REJECTED_LEG_EXTENSIONS,
+    },
+    state::{EscrowConfig, EscrowState, OfferTerms},
 };
-use crate::state::EscrowState;
 
 #[derive(Accounts)]
-#[instruction(seed:u64)]
 pub struct Take<'info> {
     #[account(mut)]
     pub taker: Signer<'info>,
     pub maker: SystemAccount<'info>,
-    pub mint_a: InterfaceAccount<'info, Mint>,
-    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// Where the maker's proceeds and any returned mint A go.
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    #[account(mut)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_a,
         associated_token::authority = taker,
+        associated_token::token_program = token_program,
     )]
-    pub taker_mint_a_ata: InterfaceAccount<'info, TokenAccount>,
+    pub taker_mint_a_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(
         mut,
         associated_token::mint = mint_b,
         associated_token::authority = taker,
+        associated_token::token_program = token_program,
     )]
-    pub taker_mint_b_ata: InterfaceAccount<'info, TokenAccount>,
+    pub taker_mint_b_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_b,
-        associated_token::authority = maker,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
     )]
-    pub maker_mint_b_ata: InterfaceAccount<'info, TokenAccount>,
+    pub recipient_mint_b_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub recipient_mint_a_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(
         mut,
         has_one = maker,
+        has_one = recipient,
         has_one = mint_a,
         has_one = mint_b,
-        close = taker,
-        seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        constraint = escrow.can_take(taker.key) @ EscrowError::InvalidTaker,
+        constraint = !escrow.is_vesting() @ EscrowError::OfferTaken,
+        constraint = !escrow.is_expired(Clock::get()?.unix_timestamp) @ EscrowError::OfferExpired,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
     )]
-    pub escrow: Account<'info, EscrowState>,
+    pub escrow: Box<Account<'info, EscrowState>>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        seeds = [b"sol_vault", escrow.key().as_ref()],
+        bump = escrow.sol_vault_bump,
+    )]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Box<Account<'info, EscrowConfig>>,
+    #[account(mut, address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub treasury_mint_b_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
@@ -55,31 +102,164 @@ pub struct Take<'info> {
 
 
 impl<'info> Take<'info> {
-    pub fn transfer_to_maker(&mut self,) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-        let receive_amount = self.escrow.receive_amount;
-        let cpi_accounts = TransferChecked {
-            from: self.taker_mint_b_ata.to_account_info(),
-            to: self.maker_mint_b_ata.to_account_info(),
-            authority: self.taker.to_account_info(),
-            mint: self.mint_b.to_account_info(),
-        };
-
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer_checked(cpi_ctx, receive_amount, self.mint_b.decimals)?;
+    /// A native side moves lamports, so its token accounts must be left out.
+    pub fn check_native_accounts(&self) -> Result<()> {
+        require!(
+            !self.escrow.native_a()
+                || (self.taker_mint_a_ata.is_none() && self.recipient_mint_a_ata.is_none() && self.vault.is_none()),
+            EscrowError::WrappedSolNotSupported
+        );
+        require!(
+            !self.escrow.native_b()
+                || (self.taker_mint_b_ata.is_none()
+                    && self.recipient_mint_b_ata.is_none()
+                    && self.treasury_mint_b_ata.is_none()),
+            EscrowError::WrappedSolNotSupported
+        );
         Ok(())
     }
 
-    pub fn transfer_to_taker(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-        let deposit_amount = self.vault.amount;
-        let cpi_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            to: self.taker_mint_a_ata.to_account_info(),
-            mint: self.mint_a.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
+    /// Fills `amount` of the remaining mint A deposit. Returns the mint B cost,
+    /// proportional to the remaining terms (the current price for an auction)
+    /// and rounded up in the maker's favour, and whether this fill completes
+    /// the offer.
+    ///
+    /// `expected_deposit` is the remaining deposit the taker saw, `max_cost`
+    /// the most mint B they will pay and `expected_terms` the rest of the offer
+    /// as `EscrowState::terms` returned it, so a maker recreating the offer
+    /// with worse terms (smaller offered legs, bigger requested ones, a vesting
+    /// cliff) makes the take fail instead of filling on them.
+    pub fn fill(
+        &mut self,
+        amount: u64,
+        expected_deposit: u64,
+        max_cost: u64,
+        expected_terms: &OfferTerms,
+    ) -> Result<(u64, bool)> {
+        let remaining = self.escrow.deposit_amount;
+        require!(remaining == expected_deposit, EscrowError::TermsChanged);
+        require!(self.escrow.terms() == *expected_terms, EscrowError::TermsChanged);
+        require!(self.vault_balance()? >= remaining, EscrowError::VaultBalanceMismatch);
+        require!(amount > 0 && amount <= remaining, EscrowError::InvalidFillAmount);
+        require!(self.escrow.allows_partial_fill() || amount == remaining, EscrowError::PartialFillNotAllowed);
+
+        let receive_amount = self.escrow.receive_amount_at(Clock::get()?.unix_timestamp);
+        let cost = (amount as u128)
+            .checked_mul(receive_amount as u128)
+            .and_then(|v| v.checked_add(remaining as u128 - 1))
+            .map(|v| v / remaining as u128)
+            .and_then(|v| u64::try_from(v).ok())
+            .ok_or(EscrowError::Overflow)?;
+        require!(cost <= max_cost, EscrowError::SlippageExceeded);
+
+        self.escrow.receive_amount = receive_amount - cost;
+        self.escrow.deposit_amount -= amount;
+        Ok((cost, amount == remaining))
+    }
+
+    /// Mint A held for the offer, excluding the SOL vault's own rent.
+    fn vault_balance(&self) -> Result<u64> {
+        if self.escrow.native_a() {
+            let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            Ok(sol_vault.lamports().saturating_sub(sol_vault_rent()?))
+        } else {
+            let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            Ok(vault.amount)
+        }
+    }
+
+    /// Pays the maker's recipient for the fill, less the protocol fee which goes
+    /// to the treasury.
+    pub fn transfer_to_maker(&mut self, receive_amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let fee = self.config.fee(receive_amount).ok_or(EscrowError::Overflow)?;
+
+        if self.escrow.native_b() {
+            let system_program = self.system_program.to_account_info();
+            let taker = self.taker.to_account_info();
+            transfer_lamports(&system_program, &taker, &self.recipient.to_account_info(), receive_amount - fee, &[])?;
+            if fee > 0 {
+                transfer_lamports(&system_program, &taker, &self.treasury.to_account_info(), fee, &[])?;
+            }
+            return Ok(());
+        }
+
+        let taker_mint_b_ata = self.taker_mint_b_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+        let recipient_mint_b_ata = self.recipient_mint_b_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &taker_mint_b_ata.to_account_info(),
+            &recipient_mint_b_ata.to_account_info(),
+            &self.mint_b.to_account_info(),
+            &self.taker.to_account_info(),
+            hook_accounts,
+            receive_amount - fee,
+            self.mint_b.decimals,
+            &[],
+        )?;
+
+        if fee > 0 {
+            let treasury_mint_b_ata = self.treasury_mint_b_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                &taker_mint_b_ata.to_account_info(),
+                &treasury_mint_b_ata.to_account_info(),
+                &self.mint_b.to_account_info(),
+                &self.taker.to_account_info(),
+                hook_accounts,
+                fee,
+                self.mint_b.decimals,
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends the filled mint A. With a transfer fee mint the taker receives
+    /// `deposit_amount` less the fee.
+    pub fn transfer_to_taker(&mut self, deposit_amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.native_a() {
+            return self.send_from_sol_vault(&self.taker.to_account_info(), deposit_amount);
+        }
+
+        let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+        let taker_mint_a_ata = self.taker_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &vault.to_account_info(),
+            &taker_mint_a_ata.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.escrow.to_account_info(),
+            hook_accounts,
+            deposit_amount,
+            self.mint_a.decimals,
+            seeds,
+        )
+    }
+
+    /// Swaps the extra basket legs. Expects `[mint, vault, taker ata, recipient
+    /// ata]` per offered leg followed by `[mint, taker ata, recipient ata]` per
+    /// requested leg in `remaining_accounts`.
+    pub fn settle_basket(&mut self, remaining: &[AccountInfo<'info>]) -> Result<()> {
+        let offered_len = self.escrow.offered.len() * 4;
+        require!(
+            remaining.len() == offered_len + self.escrow.requested.len() * 3,
+            EscrowError::InvalidLegAccount
+        );
+        let (offered, requested) = remaining.split_at(offered_len);
 
+        let token_program = self.token_program.key();
+        let escrow_key = self.escrow.key();
         let seeds: &[&[&[u8]]] = &[
             &[
                 b"escrow",
@@ -88,18 +268,194 @@ impl<'info> Take<'info> {
                 &[self.escrow.bump],
             ],
         ];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
-        transfer_checked(cpi_ctx, deposit_amount, self.mint_a.decimals)?;
+
+        for (leg, accounts) in self.escrow.offered.iter().zip(offered.chunks_exact(4)) {
+            let (mint, vault, taker_ata, recipient_ata) = (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+            require_keys_eq!(leg.mint, *mint.key, EscrowError::InvalidLegAccount);
+            check_ata(vault, &escrow_key, mint.key, &token_program)?;
+            check_ata(taker_ata, self.taker.key, mint.key, &token_program)?;
+            check_ata(recipient_ata, self.recipient.key, mint.key, &token_program)?;
+            let decimals = mint_decimals(mint, &token_program)?;
+
+            let balance = token_balance(vault)?;
+            require!(balance >= leg.amount, EscrowError::VaultBalanceMismatch);
+
+            create_ata(
+                &self.taker.to_account_info(),
+                taker_ata,
+                &self.taker.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                &self.token_program.to_account_info(),
+                &self.associated_token_program.to_account_info(),
+            )?;
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                vault,
+                taker_ata,
+                mint,
+                &self.escrow.to_account_info(),
+                &[],
+                leg.amount,
+                decimals,
+                seeds,
+            )?;
+
+            if balance > leg.amount {
+                create_ata(
+                    &self.taker.to_account_info(),
+                    recipient_ata,
+                    &self.recipient.to_account_info(),
+                    mint,
+                    &self.system_program.to_account_info(),
+                    &self.token_program.to_account_info(),
+                    &self.associated_token_program.to_account_info(),
+                )?;
+                transfer_tokens(
+                    &self.token_program.to_account_info(),
+                    vault,
+                    recipient_ata,
+                    mint,
+                    &self.escrow.to_account_info(),
+                    &[],
+                    balance - leg.amount,
+                    decimals,
+                    seeds,
+                )?;
+            }
+
+            close_token_account(
+                &self.token_program.to_account_info(),
+                vault,
+                &self.taker.to_account_info(),
+                &self.escrow.to_account_info(),
+                seeds,
+            )?;
+        }
+
+        for (leg, accounts) in self.escrow.requested.iter().zip(requested.chunks_exact(3)) {
+            let (mint, taker_ata, recipient_ata) = (&accounts[0], &accounts[1], &accounts[2]);
+            require_keys_eq!(leg.mint, *mint.key, EscrowError::InvalidLegAccount);
+            check_mint_extensions(mint, REJECTED_LEG_EXTENSIONS)?;
+            check_ata(taker_ata, self.taker.key, mint.key, &token_program)?;
+            check_ata(recipient_ata, self.recipient.key, mint.key, &token_program)?;
+            let decimals = mint_decimals(mint, &token_program)?;
+
+            create_ata(
+                &self.taker.to_account_info(),
+                recipient_ata,
+                &self.recipient.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                &self.token_program.to_account_info(),
+                &self.associated_token_program.to_account_info(),
+            )?;
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                taker_ata,
+                recipient_ata,
+                mint,
+                &self.taker.to_account_info(),
+                &[],
+                leg.amount,
+                decimals,
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn send_from_sol_vault(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+        let escrow_key = self.escrow.key();
+        let seeds: &[&[&[u8]]] = &[&[b"sol_vault", escrow_key.as_ref(), &[self.escrow.sol_vault_bump]]];
+        transfer_lamports(
+            &self.system_program.to_account_info(),
+            &sol_vault.to_account_info(),
+            to,
+            amount,
+            seeds,
+        )
+    }
+
+    pub fn emit_taken(&self, amount: u64, cost: u64) {
+        emit!(OfferTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            amount,
+            cost,
+            remaining_deposit: self.escrow.deposit_amount,
+            remaining_receive: self.escrow.receive_amount,
+        });
+    }
+
+    /// Keeps the filled mint A in the vault, released to the taker over time
+    /// through `claim_vested`.
+    pub fn start_vesting(&mut self, amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let taker = self.taker.key();
+        let vesting = self.escrow.vesting.as_mut().ok_or(EscrowError::InvalidVesting)?;
+        vesting.start = now;
+        vesting.beneficiary = taker;
+        vesting.total = amount;
         Ok(())
     }
 
+    /// Anything in the vault on top of `keep` goes back to the maker's recipient.
+    pub fn return_excess(&mut self, keep: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.native_a() {
+            let excess = self.vault_balance()?.saturating_sub(keep);
+            if excess > 0 {
+                self.send_from_sol_vault(&self.recipient.to_account_info(), excess)?;
+            }
+            return Ok(());
+        }
+
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingAccount)?;
+        vault.reload()?;
+        let excess = vault.amount.saturating_sub(keep);
+        if excess == 0 {
+            return Ok(());
+        }
+        let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+        let recipient_mint_a_ata = self.recipient_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+        let seeds: &[&[&[u8]]] = &[
+            &[
+                b"escrow",
+                self.maker.key.as_ref(),
+                &self.escrow.seed.to_le_bytes(),
+                &[self.escrow.bump],
+            ],
+        ];
+        transfer_tokens(
+            &self.token_program.to_account_info(),
+            &vault.to_account_info(),
+            &recipient_mint_a_ata.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &self.escrow.to_account_info(),
+            hook_accounts,
+            excess,
+            self.mint_a.decimals,
+            seeds,
+        )
+    }
+
     pub fn close_vault(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_account = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.taker.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
+        if self.escrow.native_a() {
+            let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            self.send_from_sol_vault(&self.taker.to_account_info(), sol_vault.lamports())?;
+            return self.escrow.close(self.taker.to_account_info());
+        }
+
+        let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+        harvest_withheld(
+            &self.token_program.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &vault.to_account_info(),
+        )?;
+
         let seeds: &[&[&[u8]]] = &[
             &[
                 b"escrow",
@@ -108,8 +464,14 @@ impl<'info> Take<'info> {
                 &[self.escrow.bump],
             ],
         ];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_account, seeds);
-        close_account(cpi_ctx)?;
+        close_token_account(
+            &self.token_program.to_account_info(),
+            &vault.to_account_info(),
+            &self.taker.to_account_info(),
+            &self.escrow.to_account_info(),
+            seeds,
+        )?;
+        self.escrow.close(self.taker.to_account_info())?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,180 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    errors::EscrowError,
+    events::OfferUpdated,
+    helpers::{transfer_lamports, transfer_tokens},
+    state::EscrowState,
+};
+
+#[derive(Accounts)]
+pub struct UpdateOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_mint_a_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = mint_a,
+        constraint = !escrow.is_vesting() @ EscrowError::OfferTaken,
+        constraint = escrow.arbitration.is_none() @ EscrowError::InvalidArbitration,
+        constraint = escrow.milestones.is_none() @ EscrowError::InvalidMilestones,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, EscrowState>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"sol_vault", escrow.key().as_ref()],
+        bump = escrow.sol_vault_bump,
+    )]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> UpdateOffer<'info> {
+    /// An auction's price and window are fixed once it starts.
+    pub fn update_terms(&mut self, receive_amount: Option<u64>, expires_at: Option<i64>) -> Result<()> {
+        require!(
+            !self.escrow.native_a() || (self.maker_mint_a_ata.is_none() && self.vault.is_none()),
+            EscrowError::WrappedSolNotSupported
+        );
+        require!(
+            self.escrow.auction.is_none() || (receive_amount.is_none() && expires_at.is_none()),
+            EscrowError::InvalidAuction
+        );
+
+        if let Some(receive_amount) = receive_amount {
+            require!(receive_amount > 0, EscrowError::InvalidAmount);
+            self.escrow.receive_amount = receive_amount;
+        }
+
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at >= self.escrow.expires_at && expires_at > Clock::get()?.unix_timestamp,
+                EscrowError::InvalidExpiry
+            );
+            self.escrow.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    /// Adds to the deposit. As in `make`, only what reaches the vault after
+    /// any transfer fee is counted.
+    pub fn top_up(&mut self, amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let received = if self.escrow.native_a() {
+            let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            transfer_lamports(
+                &self.system_program.to_account_info(),
+                &self.maker.to_account_info(),
+                &sol_vault.to_account_info(),
+                amount,
+                &[],
+            )?;
+            amount
+        } else {
+            let maker_mint_a_ata = self.maker_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+            let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            let before = vault.amount;
+
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                &maker_mint_a_ata.to_account_info(),
+                &vault.to_account_info(),
+                &self.mint_a.to_account_info(),
+                &self.maker.to_account_info(),
+                hook_accounts,
+                amount,
+                self.mint_a.decimals,
+                &[],
+            )?;
+
+            let vault = self.vault.as_mut().ok_or(EscrowError::MissingAccount)?;
+            vault.reload()?;
+            vault.amount - before
+        };
+
+        self.escrow.deposit_amount = self.escrow.deposit_amount
+            .checked_add(received)
+            .ok_or(EscrowError::Overflow)?;
+        Ok(())
+    }
+
+    /// Withdrawing the whole deposit is a refund, so some of it must stay.
+    pub fn withdraw(&mut self, amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        require!(amount < self.escrow.deposit_amount, EscrowError::InvalidAmount);
+
+        if self.escrow.native_a() {
+            let sol_vault = self.sol_vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+            let escrow_key = self.escrow.key();
+            let seeds: &[&[&[u8]]] = &[&[b"sol_vault", escrow_key.as_ref(), &[self.escrow.sol_vault_bump]]];
+            transfer_lamports(
+                &self.system_program.to_account_info(),
+                &sol_vault.to_account_info(),
+                &self.maker.to_account_info(),
+                amount,
+                seeds,
+            )?;
+        } else {
+            let maker_mint_a_ata = self.maker_mint_a_ata.as_ref().ok_or(EscrowError::MissingAccount)?;
+            let vault = self.vault.as_ref().ok_or(EscrowError::MissingAccount)?;
+
+            let seeds: &[&[&[u8]]] = &[
+                &[
+                    b"escrow",
+                    self.maker.key.as_ref(),
+                    &self.escrow.seed.to_le_bytes(),
+                    &[self.escrow.bump],
+                ],
+            ];
+            transfer_tokens(
+                &self.token_program.to_account_info(),
+                &vault.to_account_info(),
+                &maker_mint_a_ata.to_account_info(),
+                &self.mint_a.to_account_info(),
+                &self.escrow.to_account_info(),
+                hook_accounts,
+                amount,
+                self.mint_a.decimals,
+                seeds,
+            )?;
+        }
+
+        self.escrow.deposit_amount -= amount;
+        Ok(())
+    }
+
+    pub fn emit_update(&self) -> Result<()> {
+        emit!(OfferUpdated {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            receive_amount: self.escrow.receive_amount,
+            deposit_amount: self.escrow.deposit_amount,
+            expires_at: self.escrow.expires_at,
+        });
+        Ok(())
+    }
+}
@@ -1,9 +1,14 @@
 use anchor_lang::prelude::*;
 
+pub mod errors;
+pub mod events;
+pub mod helpers;
 pub mod instructions;
 pub mod state;
 
+use crate::helpers::split_hook_accounts;
 use crate::instructions::*;
+use crate::state::OfferTerms;
 
 declare_id!("A2rhTqfm2uHyChiDurS66FUV42fURpkWHeGus7qRQDLq");
 
@@ -11,23 +16,97 @@ declare_id!("A2rhTqfm2uHyChiDurS66FUV42fURpkWHeGus7qRQDLq");
 pub mod escrow {
     use super::*;
 
-    pub fn make(ctx: Context<Make>, seed: u64, receive_amount: u64, deposit_amount: u64,) -> Result<()> {
-       ctx.accounts.make(seed, receive_amount, ctx.bumps)?;
-       ctx.accounts.deposit(deposit_amount)?;
+    pub fn initialize_config(ctx: Context<InitializeConfig>, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.init_config(fee_bps, treasury, &ctx.bumps)
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.update_config(fee_bps, treasury)
+    }
+
+    pub fn make<'info>(ctx: Context<'_, '_, 'info, 'info, Make<'info>>, seed: u64, receive_amount: u64, deposit_amount: u64, expires_at: i64, terms: OfferTerms,) -> Result<()> {
+       let (legs, hooks) = split_hook_accounts(ctx.remaining_accounts, terms.offered.len() * 3)?;
+       ctx.accounts.make(seed, receive_amount, deposit_amount, expires_at, terms, ctx.bumps)?;
+       ctx.accounts.deposit(deposit_amount, hooks)?;
+       ctx.accounts.deposit_basket(legs)?;
+       ctx.accounts.emit_created();
         Ok(())
     }
 
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.transfer_to_maker()?;
-        ctx.accounts.transfer_to_taker()?;
-        ctx.accounts.close_vault()?;
+    pub fn take<'info>(ctx: Context<'_, '_, 'info, 'info, Take<'info>>, amount: u64, expected_deposit: u64, max_cost: u64, expected_terms: OfferTerms) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let (legs, hooks) = split_hook_accounts(
+            ctx.remaining_accounts,
+            escrow.offered.len() * 4 + escrow.requested.len() * 3,
+        )?;
+        ctx.accounts.check_native_accounts()?;
+        let (cost, filled) = ctx.accounts.fill(amount, expected_deposit, max_cost, &expected_terms)?;
+        ctx.accounts.transfer_to_maker(cost, hooks)?;
+        ctx.accounts.settle_basket(legs)?;
+        ctx.accounts.emit_taken(amount, cost);
+        if ctx.accounts.escrow.vesting.is_some() {
+            ctx.accounts.start_vesting(amount)?;
+            ctx.accounts.return_excess(amount, hooks)?;
+            return Ok(());
+        }
+        ctx.accounts.transfer_to_taker(amount, hooks)?;
+        if filled {
+            ctx.accounts.return_excess(0, hooks)?;
+            ctx.accounts.close_vault()?;
+        }
         Ok(())
     }
 
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        ctx.accounts.refund()?;
+    pub fn refund<'info>(ctx: Context<'_, '_, 'info, 'info, Refund<'info>>) -> Result<()> {
+        let (legs, hooks) = split_hook_accounts(ctx.remaining_accounts, ctx.accounts.escrow.offered.len() * 3)?;
+        ctx.accounts.refund(hooks)?;
+        ctx.accounts.refund_basket(legs)?;
         ctx.accounts.close()?;
+        ctx.accounts.emit_refunded();
         Ok(())
     }
-}
 
+    pub fn refund_many<'info>(ctx: Context<'_, '_, 'info, 'info, RefundMany<'info>>) -> Result<()> {
+        ctx.accounts.refund_many(ctx.remaining_accounts)
+    }
+
+    pub fn update_offer<'info>(ctx: Context<'_, '_, 'info, 'info, UpdateOffer<'info>>, receive_amount: Option<u64>, top_up: u64, withdraw: u64, expires_at: Option<i64>) -> Result<()> {
+        ctx.accounts.update_terms(receive_amount, expires_at)?;
+        ctx.accounts.top_up(top_up, ctx.remaining_accounts)?;
+        ctx.accounts.withdraw(withdraw, ctx.remaining_accounts)?;
+        ctx.accounts.emit_update()
+    }
+
+    pub fn claim_vested<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimVested<'info>>) -> Result<()> {
+        ctx.accounts.claim(ctx.remaining_accounts)?;
+        ctx.accounts.close_if_done(ctx.remaining_accounts)
+    }
+
+    pub fn open_dispute(ctx: Context<OpenDispute>) -> Result<()> {
+        ctx.accounts.open_dispute()
+    }
+
+    pub fn approve_release<'info>(ctx: Context<'_, '_, 'info, 'info, Settle<'info>>) -> Result<()> {
+        if ctx.accounts.approve()? {
+            let amount = ctx.accounts.vault.amount;
+            ctx.accounts.settle(amount, ctx.remaining_accounts)?;
+        }
+        Ok(())
+    }
+
+    pub fn resolve_dispute<'info>(ctx: Context<'_, '_, 'info, 'info, Settle<'info>>, beneficiary_amount: u64) -> Result<()> {
+        ctx.accounts.check_resolution(beneficiary_amount)?;
+        ctx.accounts.settle(beneficiary_amount, ctx.remaining_accounts)
+    }
+
+    pub fn approve_milestone(ctx: Context<ApproveMilestone>, index: u8) -> Result<()> {
+        ctx.accounts.approve(index)
+    }
+
+    pub fn release_milestone<'info>(ctx: Context<'_, '_, 'info, 'info, ReleaseMilestone<'info>>) -> Result<()> {
+        if ctx.accounts.release(ctx.remaining_accounts)? {
+            ctx.accounts.close()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowConfig {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub treasury: Pubkey,
+    pub bump: u8,
+}
+
+impl EscrowConfig {
+    pub fn fee(&self, amount: u64) -> Option<u64> {
+        let fee = (amount as u128).checked_mul(self.fee_bps as u128)? / 10_000;
+        u64::try_from(fee).ok()
+    }
+}
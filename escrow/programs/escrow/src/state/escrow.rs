@@ -1,11 +1,314 @@
 use anchor_lang::prelude::*;
+
+use crate::{errors::EscrowError, helpers::is_native};
+
+/// Extra mints an offer can lock up or ask for on top of `mint_a`/`mint_b`.
+pub const MAX_BASKET_LEGS: usize = 4;
+
+pub const MAX_MILESTONES: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+impl BasketLeg {
+    /// Legs must be SPL mints that do not repeat each other or the primary mint
+    /// of their side.
+    pub fn validate(primary: &Pubkey, legs: &[BasketLeg]) -> Result<()> {
+        require!(legs.len() <= MAX_BASKET_LEGS, EscrowError::TooManyLegs);
+        for (i, leg) in legs.iter().enumerate() {
+            require!(
+                leg.amount > 0
+                    && !is_native(&leg.mint)
+                    && leg.mint != *primary
+                    && !legs[..i].iter().any(|l| l.mint == leg.mint),
+                EscrowError::InvalidLeg
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Release schedule the maker picks for mint A, in seconds from the take.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct VestingTerms {
+    pub cliff: i64,
+    pub duration: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Vesting {
+    pub cliff: i64,
+    pub duration: i64,
+    /// Set by `take`; zero while the offer is still open.
+    pub start: i64,
+    pub beneficiary: Pubkey,
+    pub total: u64,
+    pub claimed: u64,
+}
+
+impl Vesting {
+    /// Linear release over `duration`, nothing before the cliff.
+    pub fn vested(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.start);
+        if elapsed < self.cliff {
+            return 0;
+        }
+        if elapsed >= self.duration {
+            return self.total;
+        }
+        (self.total as u128 * elapsed as u128 / self.duration as u128) as u64
+    }
+}
+
+/// Price discovery for an offer: the mint B asked falls linearly from the
+/// offer's `receive_amount` at `start_time` to `end_receive_amount` at
+/// `end_time`, after which the offer can only be refunded.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct DutchAuction {
+    pub end_receive_amount: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+impl DutchAuction {
+    pub fn validate(&self, start_receive_amount: u64, now: i64) -> Result<()> {
+        require!(
+            self.end_receive_amount > 0
+                && self.end_receive_amount <= start_receive_amount
+                && self.start_time < self.end_time
+                && self.end_time > now,
+            EscrowError::InvalidAuction
+        );
+        Ok(())
+    }
+
+    /// Price at `now`, rounded up in the maker's favour.
+    pub fn price(&self, start_receive_amount: u64, now: i64) -> u64 {
+        if now <= self.start_time {
+            return start_receive_amount;
+        }
+        if now >= self.end_time {
+            return self.end_receive_amount;
+        }
+        let drop = (start_receive_amount - self.end_receive_amount) as u128;
+        let elapsed = (now - self.start_time) as u128;
+        let window = (self.end_time - self.start_time) as u128;
+        start_receive_amount - (drop * elapsed / window) as u64
+    }
+}
+
+/// Parties of an arbitrated escrow, picked by the maker.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct ArbitrationTerms {
+    pub beneficiary: Pubkey,
+    pub arbiter: Pubkey,
+}
+
+/// Instead of being taken, an arbitrated deposit is released to the
+/// beneficiary once maker and beneficiary both approve, or split by the
+/// arbiter after either of them opens a dispute. Past `expires_at` it can only
+/// be refunded to the maker.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Arbitration {
+    pub beneficiary: Pubkey,
+    pub arbiter: Pubkey,
+    pub maker_approved: bool,
+    pub beneficiary_approved: bool,
+    pub disputed: bool,
+}
+
+/// One tranche of a milestone escrow and who signs it off.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct Tranche {
+    pub amount: u64,
+    pub approver: Pubkey,
+}
+
+/// Milestone schedule picked by the maker, paid out in order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct MilestoneTerms {
+    pub beneficiary: Pubkey,
+    pub tranches: Vec<Tranche>,
+}
+
+/// Optional terms of an offer, passed to `make` as one argument. Left at their
+/// defaults they make a plain offer anyone can take.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq)]
+pub struct OfferTerms {
+    /// Makes the offer private to this taker.
+    pub taker: Option<Pubkey>,
+    pub offered: Vec<BasketLeg>,
+    pub requested: Vec<BasketLeg>,
+    pub vesting: Option<VestingTerms>,
+    pub auction: Option<DutchAuction>,
+    pub arbitration: Option<ArbitrationTerms>,
+    pub milestones: Option<MilestoneTerms>,
+    /// Where proceeds and refunds go instead of the maker.
+    pub recipient: Option<Pubkey>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Milestone {
+    pub amount: u64,
+    pub approver: Pubkey,
+    pub approved: bool,
+}
+
+/// Instead of being taken, a milestone escrow pays its deposit to the
+/// beneficiary one tranche at a time, in order, each once its approver has
+/// signed off. Past `expires_at` what is left can only be refunded to the
+/// maker.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Milestones {
+    pub beneficiary: Pubkey,
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<Milestone>,
+    /// Number of milestones paid out so far; the next one to release.
+    pub released: u8,
+}
+
+impl MilestoneTerms {
+    /// Amounts must be positive and add up to the deposit.
+    pub fn validate(&self, deposit_amount: u64) -> Result<()> {
+        let tranches = &self.tranches;
+        require!(
+            (1..=MAX_MILESTONES).contains(&tranches.len()) && tranches.iter().all(|t| t.amount > 0),
+            EscrowError::InvalidMilestones
+        );
+        let total = tranches
+            .iter()
+            .try_fold(0u64, |total, m| total.checked_add(m.amount))
+            .ok_or(EscrowError::Overflow)?;
+        require!(total == deposit_amount, EscrowError::InvalidMilestones);
+        Ok(())
+    }
+}
+
+impl Milestones {
+    pub fn is_last(&self) -> bool {
+        self.released as usize + 1 == self.milestones.len()
+    }
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct EscrowState {
     pub seed: u64,
     pub maker: Pubkey,
+    /// Receives mint B from takes, and mint A and rent on refunds. The maker
+    /// unless they picked another wallet.
+    pub recipient: Pubkey,
     pub mint_a: Pubkey,
     pub mint_b: Pubkey,
     pub receive_amount: u64,
+    pub deposit_amount: u64,
+    pub expires_at: i64,
+    pub taker: Option<Pubkey>,
+    #[max_len(MAX_BASKET_LEGS)]
+    pub offered: Vec<BasketLeg>,
+    #[max_len(MAX_BASKET_LEGS)]
+    pub requested: Vec<BasketLeg>,
+    pub vesting: Option<Vesting>,
+    pub auction: Option<DutchAuction>,
+    pub arbitration: Option<Arbitration>,
+    pub milestones: Option<Milestones>,
+    pub sol_vault_bump: u8,
     pub bump: u8,
-}
\ No newline at end of file
+}
+
+impl EscrowState {
+    /// An auction that ran its window without a taker is expired as well.
+    pub fn is_expired(&self, now: i64) -> bool {
+        Self::expired(self.expires_at, self.auction.as_ref(), now)
+    }
+
+    /// The rule behind `is_expired`, for off-chain readers that hold an
+    /// offer's terms but not the account.
+    pub fn expired(expires_at: i64, auction: Option<&DutchAuction>, now: i64) -> bool {
+        now > expires_at || auction.is_some_and(|a| now > a.end_time)
+    }
+
+    /// The optional terms the offer was made with, as `make` took them.
+    pub fn terms(&self) -> OfferTerms {
+        OfferTerms {
+            taker: self.taker,
+            offered: self.offered.clone(),
+            requested: self.requested.clone(),
+            vesting: self.vesting.as_ref().map(|v| VestingTerms { cliff: v.cliff, duration: v.duration }),
+            auction: self.auction.clone(),
+            arbitration: self.arbitration.as_ref().map(|a| ArbitrationTerms {
+                beneficiary: a.beneficiary,
+                arbiter: a.arbiter,
+            }),
+            milestones: self.milestones.as_ref().map(|m| MilestoneTerms {
+                beneficiary: m.beneficiary,
+                tranches: m
+                    .milestones
+                    .iter()
+                    .map(|t| Tranche { amount: t.amount, approver: t.approver })
+                    .collect(),
+            }),
+            recipient: (self.recipient != self.maker).then_some(self.recipient),
+        }
+    }
+
+    /// Mint B asked for the remaining deposit at `now`.
+    pub fn receive_amount_at(&self, now: i64) -> u64 {
+        match &self.auction {
+            Some(auction) => auction.price(self.receive_amount, now),
+            None => self.receive_amount,
+        }
+    }
+
+    /// Mint A is deposited as lamports in the SOL vault instead of a token vault.
+    pub fn native_a(&self) -> bool {
+        is_native(&self.mint_a)
+    }
+
+    /// Mint B is paid as lamports straight to the maker.
+    pub fn native_b(&self) -> bool {
+        is_native(&self.mint_b)
+    }
+
+    pub fn is_basket(&self) -> bool {
+        !self.offered.is_empty() || !self.requested.is_empty()
+    }
+
+    /// Baskets, vesting offers and auctions have a single taker, so they fill
+    /// in full.
+    pub fn allows_partial_fill(&self) -> bool {
+        !self.is_basket() && self.vesting.is_none() && self.auction.is_none()
+    }
+
+    /// A vesting offer stays open after `take` until the taker has claimed everything.
+    pub fn is_vesting(&self) -> bool {
+        self.vesting.as_ref().is_some_and(|v| v.start != 0)
+    }
+
+    /// Arbitrated and milestone escrows pay a fixed beneficiary instead of
+    /// being taken, and the maker cannot pull them back before they expire.
+    pub fn has_beneficiary(&self) -> bool {
+        self.arbitration.is_some() || self.milestones.is_some()
+    }
+
+    pub fn beneficiary(&self) -> Option<Pubkey> {
+        self.arbitration
+            .as_ref()
+            .map(|a| a.beneficiary)
+            .or(self.milestones.as_ref().map(|m| m.beneficiary))
+    }
+
+    pub fn can_take(&self, taker: &Pubkey) -> bool {
+        !self.has_beneficiary() && (self.taker.is_none() || self.taker == Some(*taker))
+    }
+
+    /// The maker or the beneficiary of an arbitrated escrow.
+    pub fn is_party(&self, key: &Pubkey) -> bool {
+        self.arbitration
+            .as_ref()
+            .is_some_and(|a| *key == self.maker || *key == a.beneficiary)
+    }
+}
@@ -1,2 +1,4 @@
 mod escrow;
-pub use escrow::*;
\ No newline at end of file
+mod config;
+pub use escrow::*;
+pub use config::*;
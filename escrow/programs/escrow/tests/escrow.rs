@@ -0,0 +1,1679 @@
+//! Runtime tests for the make / take / refund paths, run against the program
+//! built natively inside `solana-program-test` together with the SPL Token,
+//! Token-2022 and associated token programs it ships with.
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, spl_associated_token_account},
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        state::{Account as TokenAccount, Mint},
+    },
+};
+use escrow::{
+    errors::EscrowError,
+    state::{
+        ArbitrationTerms, BasketLeg, DutchAuction, EscrowState, MilestoneTerms, OfferTerms, Tranche, VestingTerms,
+    },
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    account_info::AccountInfo,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+const FEE_BPS: u16 = 100;
+const DECIMALS: u8 = 6;
+const DEPOSIT: u64 = 1_000_000;
+const RECEIVE: u64 = 2_000_000;
+const TRANSFER_FEE_BPS: u16 = 100;
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entrypoint ties the account infos to the slice's lifetime.
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    escrow::entry(program_id, accounts, data)
+}
+
+struct Env {
+    ctx: ProgramTestContext,
+    maker: Keypair,
+    taker: Keypair,
+    treasury: Pubkey,
+}
+
+/// A token offer of `mint_a` for `mint_b`, both owned by `token_program`.
+struct Offer {
+    seed: u64,
+    /// Where proceeds and refunds go; the maker unless a test changes it.
+    recipient: Pubkey,
+    escrow: Pubkey,
+    vault: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    token_program: Pubkey,
+}
+
+impl Offer {
+    fn new(maker: &Pubkey, seed: u64, mint_a: Pubkey, mint_b: Pubkey, token_program: Pubkey) -> Self {
+        let (escrow, _) = Pubkey::find_program_address(
+            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
+            &escrow::ID,
+        );
+        let vault = get_associated_token_address_with_program_id(&escrow, &mint_a, &token_program);
+        Self { seed, recipient: *maker, escrow, vault, mint_a, mint_b, token_program }
+    }
+
+    fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, mint, &self.token_program)
+    }
+
+    /// The ATA for a token side, `None` for a side traded as native SOL.
+    fn token_ata(&self, owner: &Pubkey, mint: &Pubkey) -> Option<Pubkey> {
+        (*mint != spl_token::native_mint::ID).then(|| self.ata(owner, mint))
+    }
+
+    fn native_a(&self) -> bool {
+        self.mint_a == spl_token::native_mint::ID
+    }
+
+    fn sol_vault(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"sol_vault", self.escrow.as_ref()], &escrow::ID).0
+    }
+}
+
+/// Starts the test validator without the fee config. The program is loaded
+/// as a builtin, so the account holding its upgrade authority is written
+/// directly, with the context payer as the authority.
+async fn start() -> Env {
+    let mut program = ProgramTest::new("escrow", escrow::ID, processor!(process_instruction));
+    let maker = Keypair::new();
+    let taker = Keypair::new();
+    for user in [&maker, &taker] {
+        program.add_account(
+            user.pubkey(),
+            Account::new(10 * LAMPORTS_PER_SOL, 0, &system_program::ID),
+        );
+    }
+    let mut env = Env {
+        ctx: program.start_with_context().await,
+        maker,
+        taker,
+        treasury: Pubkey::new_unique(),
+    };
+
+    let program_data = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(env.ctx.payer.pubkey()),
+    };
+    let account = AccountSharedData::new_data(LAMPORTS_PER_SOL, &program_data, &bpf_loader_upgradeable::ID).unwrap();
+    env.ctx.set_account(&program_data_address(), &account);
+    env
+}
+
+/// Starts the test validator with the fee config set to `FEE_BPS`.
+async fn setup() -> Env {
+    let mut env = start().await;
+    let ix = initialize_config_ix(&env.ctx.payer.pubkey(), FEE_BPS, &env.treasury);
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+    env
+}
+
+fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[escrow::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &escrow::ID).0
+}
+
+fn initialize_config_ix(admin: &Pubkey, fee_bps: u16, treasury: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::InitializeConfig {
+            admin: *admin,
+            program_data: program_data_address(),
+            config: config_address(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::InitializeConfig { fee_bps, treasury: *treasury }.data(),
+    }
+}
+
+fn update_config_ix(admin: &Pubkey, fee_bps: u16, treasury: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::UpdateConfig { admin: *admin, config: config_address() }.to_account_metas(None),
+        data: escrow::instruction::UpdateConfig { fee_bps, treasury: *treasury }.data(),
+    }
+}
+
+/// Sends `ixs` with the context payer paying the fees, so the balances of the
+/// parties only move by what the program does.
+async fn send(ctx: &mut ProgramTestContext, ixs: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+    // a fresh blockhash keeps a retried instruction from being deduplicated
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+async fn create_mint(env: &mut Env, token_program: &Pubkey, extensions: &[ExtensionType]) -> Pubkey {
+    let mint = Keypair::new();
+    let authority = env.ctx.payer.pubkey();
+    let space = ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap();
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+
+    let mut ixs = vec![system_instruction::create_account(
+        &authority,
+        &mint.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        token_program,
+    )];
+    for extension in extensions {
+        ixs.push(
+            match extension {
+                ExtensionType::TransferFeeConfig => transfer_fee::instruction::initialize_transfer_fee_config(
+                    token_program,
+                    &mint.pubkey(),
+                    Some(&authority),
+                    Some(&authority),
+                    TRANSFER_FEE_BPS,
+                    u64::MAX,
+                ),
+                ExtensionType::NonTransferable => {
+                    spl_token_2022::instruction::initialize_non_transferable_mint(token_program, &mint.pubkey())
+                }
+                ExtensionType::PermanentDelegate => {
+                    spl_token_2022::instruction::initialize_permanent_delegate(token_program, &mint.pubkey(), &authority)
+                }
+                other => panic!("no initializer for {other:?}"),
+            }
+            .unwrap(),
+        );
+    }
+    ixs.push(
+        spl_token_2022::instruction::initialize_mint2(token_program, &mint.pubkey(), &authority, None, DECIMALS)
+            .unwrap(),
+    );
+    send(&mut env.ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+/// Writes the SPL Token native mint if the test validator did not ship it.
+async fn native_mint(env: &mut Env) -> Pubkey {
+    let mint = spl_token::native_mint::ID;
+    if env.ctx.banks_client.get_account(mint).await.unwrap().is_none() {
+        let state = Mint { decimals: spl_token::native_mint::DECIMALS, is_initialized: true, ..Mint::default() };
+        let mut account = Account::new(LAMPORTS_PER_SOL, Mint::LEN, &spl_token::ID);
+        state.pack_into_slice(&mut account.data);
+        env.ctx.set_account(&mint, &account.into());
+    }
+    mint
+}
+
+/// Creates `owner`'s associated token account for `mint` and mints `amount` to it.
+async fn fund(env: &mut Env, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey, amount: u64) -> Pubkey {
+    let payer = env.ctx.payer.pubkey();
+    let ata = get_associated_token_address_with_program_id(owner, mint, token_program);
+    let ixs = [
+        spl_associated_token_account::instruction::create_associated_token_account(&payer, owner, mint, token_program),
+        spl_token_2022::instruction::mint_to(token_program, mint, &ata, &payer, &[], amount).unwrap(),
+    ];
+    send(&mut env.ctx, &ixs, &[]).await.unwrap();
+    ata
+}
+
+/// Mints more of mint A to the maker's existing ATA.
+async fn fund_more(env: &mut Env, offer: &Offer, amount: u64) {
+    let payer = env.ctx.payer.pubkey();
+    let ata = offer.ata(&env.maker.pubkey(), &offer.mint_a);
+    let ix = spl_token_2022::instruction::mint_to(&offer.token_program, &offer.mint_a, &ata, &payer, &[], amount)
+        .unwrap();
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+}
+
+async fn balance(env: &mut Env, token_account: &Pubkey) -> u64 {
+    let account = env.ctx.banks_client.get_account(*token_account).await.unwrap().unwrap();
+    StateWithExtensions::<TokenAccount>::unpack(&account.data).unwrap().base.amount
+}
+
+async fn lamports(env: &mut Env, address: &Pubkey) -> u64 {
+    env.ctx.banks_client.get_balance(*address).await.unwrap()
+}
+
+async fn is_closed(env: &mut Env, address: &Pubkey) -> bool {
+    env.ctx.banks_client.get_account(*address).await.unwrap().is_none()
+}
+
+async fn now(env: &mut Env) -> i64 {
+    env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+}
+
+async fn warp_to(env: &mut Env, unix_timestamp: i64) {
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    env.ctx.set_sysvar(&clock);
+}
+
+fn assert_error(err: BanksClientError, code: u32) {
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code)),
+    );
+}
+
+fn make_ix(maker: &Pubkey, offer: &Offer, expires_at: i64) -> Instruction {
+    make_auction_ix(maker, offer, expires_at, None)
+}
+
+fn make_auction_ix(maker: &Pubkey, offer: &Offer, expires_at: i64, auction: Option<DutchAuction>) -> Instruction {
+    make_with_ix(maker, offer, expires_at, auction, None, None)
+}
+
+fn make_with_ix(
+    maker: &Pubkey,
+    offer: &Offer,
+    expires_at: i64,
+    auction: Option<DutchAuction>,
+    arbitration: Option<ArbitrationTerms>,
+    milestones: Option<MilestoneTerms>,
+) -> Instruction {
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::Make {
+            maker: *maker,
+            mint_a: offer.mint_a,
+            mint_b: offer.mint_b,
+            maker_mint_a_ata: offer.token_ata(maker, &offer.mint_a),
+            escrow: offer.escrow,
+            vault: (!offer.native_a()).then_some(offer.vault),
+            sol_vault: offer.native_a().then(|| offer.sol_vault()),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: offer.token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::Make {
+            terms: OfferTerms {
+                auction,
+                arbitration,
+                milestones,
+                recipient: (offer.recipient != *maker).then_some(offer.recipient),
+                ..OfferTerms::default()
+            },
+            ..make_args(offer, expires_at)
+        }
+        .data(),
+    }
+}
+
+/// `make` arguments for a plain offer of `DEPOSIT` for `RECEIVE`.
+fn make_args(offer: &Offer, expires_at: i64) -> escrow::instruction::Make {
+    escrow::instruction::Make {
+        seed: offer.seed,
+        receive_amount: RECEIVE,
+        deposit_amount: DEPOSIT,
+        expires_at,
+        terms: OfferTerms::default(),
+    }
+}
+
+fn take_ix(env: &Env, offer: &Offer, mint_b: Pubkey, amount: u64) -> Instruction {
+    take_by_ix(env, &env.taker.pubkey(), offer, mint_b, amount)
+}
+
+fn take_by_ix(env: &Env, taker: &Pubkey, offer: &Offer, mint_b: Pubkey, amount: u64) -> Instruction {
+    let maker = env.maker.pubkey();
+    let taker = *taker;
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::Take {
+            taker,
+            maker,
+            recipient: offer.recipient,
+            mint_a: offer.mint_a,
+            mint_b,
+            taker_mint_a_ata: offer.token_ata(&taker, &offer.mint_a),
+            taker_mint_b_ata: offer.token_ata(&taker, &mint_b),
+            recipient_mint_b_ata: offer.token_ata(&offer.recipient, &mint_b),
+            recipient_mint_a_ata: offer.token_ata(&offer.recipient, &offer.mint_a),
+            escrow: offer.escrow,
+            vault: (!offer.native_a()).then_some(offer.vault),
+            sol_vault: offer.native_a().then(|| offer.sol_vault()),
+            config: config_address(),
+            treasury: env.treasury,
+            treasury_mint_b_ata: offer.token_ata(&env.treasury, &mint_b),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: offer.token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::Take {
+            amount,
+            expected_deposit: amount,
+            max_cost: RECEIVE,
+            expected_terms: OfferTerms::default(),
+        }
+        .data(),
+    }
+}
+
+fn refund_ix(env: &Env, caller: &Pubkey, offer: &Offer) -> Instruction {
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::Refund {
+            caller: *caller,
+            maker: env.maker.pubkey(),
+            recipient: offer.recipient,
+            mint_a: offer.mint_a,
+            mint_b: offer.mint_b,
+            recipient_mint_a_ata: offer.token_ata(&offer.recipient, &offer.mint_a),
+            escrow: offer.escrow,
+            vault: (!offer.native_a()).then_some(offer.vault),
+            sol_vault: offer.native_a().then(|| offer.sol_vault()),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: offer.token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::Refund {}.data(),
+    }
+}
+
+/// Creates both mints, funds the maker with mint A and the taker with mint B,
+/// and opens an offer expiring in an hour.
+async fn open_offer(env: &mut Env, token_program: Pubkey, seed: u64) -> (Offer, i64) {
+    let mint_a = create_mint(env, &token_program, &[]).await;
+    let mint_b = create_mint(env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(env, &taker, &mint_b, &token_program, RECEIVE).await;
+
+    let offer = Offer::new(&maker, seed, mint_a, mint_b, token_program);
+    let expires_at = now(env).await + 3600;
+    send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+    (offer, expires_at)
+}
+
+async fn take(env: &mut Env, offer: &Offer, mint_b: Pubkey) -> Result<(), BanksClientError> {
+    take_amount(env, offer, mint_b, DEPOSIT).await
+}
+
+/// Takes `amount` on the terms read off the escrow account, like a client would.
+async fn take_amount(env: &mut Env, offer: &Offer, mint_b: Pubkey, amount: u64) -> Result<(), BanksClientError> {
+    let mut ix = take_ix(env, offer, mint_b, amount);
+    ix.data = escrow::instruction::Take {
+        amount,
+        expected_deposit: amount,
+        max_cost: RECEIVE,
+        expected_terms: offer_terms(env, offer).await,
+    }
+    .data();
+    send(&mut env.ctx, &[ix], &[&env.taker]).await
+}
+
+/// The offer's terms, the defaults once it is closed.
+async fn offer_terms(env: &mut Env, offer: &Offer) -> OfferTerms {
+    match env.ctx.banks_client.get_account(offer.escrow).await.unwrap() {
+        Some(account) => EscrowState::try_deserialize(&mut account.data.as_slice()).unwrap().terms(),
+        None => OfferTerms::default(),
+    }
+}
+
+async fn refund(env: &mut Env, offer: &Offer) -> Result<(), BanksClientError> {
+    let ix = refund_ix(env, &env.maker.pubkey(), offer);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await
+}
+
+async fn refund_by(env: &mut Env, caller: &Keypair, offer: &Offer) -> Result<(), BanksClientError> {
+    let ix = refund_ix(env, &caller.pubkey(), offer);
+    send(&mut env.ctx, &[ix], &[caller]).await
+}
+
+async fn assert_settled(env: &mut Env, offer: &Offer) {
+    let (maker, taker, treasury) = (env.maker.pubkey(), env.taker.pubkey(), env.treasury);
+    let fee = RECEIVE * FEE_BPS as u64 / 10_000;
+    assert_eq!(balance(env, &offer.ata(&taker, &offer.mint_a)).await, DEPOSIT);
+    assert_eq!(balance(env, &offer.ata(&taker, &offer.mint_b)).await, 0);
+    assert_eq!(balance(env, &offer.ata(&maker, &offer.mint_b)).await, RECEIVE - fee);
+    assert_eq!(balance(env, &offer.ata(&treasury, &offer.mint_b)).await, fee);
+    assert_eq!(balance(env, &offer.ata(&maker, &offer.mint_a)).await, 0);
+    assert!(is_closed(env, &offer.escrow).await);
+    assert!(is_closed(env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn take_settles_and_closes_the_offer() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+
+    // the taker pays for the maker's new ATA, the maker's lamports do not move
+    let maker = env.maker.pubkey();
+    let maker_lamports = lamports(&mut env, &maker).await;
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+    assert_settled(&mut env, &offer).await;
+    assert_eq!(lamports(&mut env, &maker).await, maker_lamports);
+}
+
+#[tokio::test]
+async fn config_needs_the_upgrade_authority() {
+    let mut env = start().await;
+    let treasury = env.treasury;
+
+    let ix = initialize_config_ix(&env.taker.pubkey(), FEE_BPS, &env.taker.pubkey());
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::NotUpgradeAuthority.into());
+
+    let ix = initialize_config_ix(&env.ctx.payer.pubkey(), FEE_BPS, &treasury);
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn admin_updates_the_config() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    let (admin, maker, new_treasury) = (env.ctx.payer.pubkey(), env.maker.pubkey(), Pubkey::new_unique());
+
+    let ix = update_config_ix(&env.taker.pubkey(), 0, &env.taker.pubkey());
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let ix = update_config_ix(&admin, 10_001, &new_treasury);
+    let err = send(&mut env.ctx, &[ix], &[]).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidFee.into());
+
+    let ix = update_config_ix(&admin, 250, &new_treasury);
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+    env.treasury = new_treasury;
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+    let fee = RECEIVE * 250 / 10_000;
+    assert_eq!(balance(&mut env, &offer.ata(&new_treasury, &offer.mint_b)).await, fee);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_b)).await, RECEIVE - fee);
+}
+
+#[tokio::test]
+async fn private_offer_only_fills_for_its_taker() {
+    let mut env = setup().await;
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &token_program, RECEIVE).await;
+    let outsider = Keypair::new();
+    let fund_outsider = system_instruction::transfer(&env.ctx.payer.pubkey(), &outsider.pubkey(), LAMPORTS_PER_SOL);
+    send(&mut env.ctx, &[fund_outsider], &[]).await.unwrap();
+    fund(&mut env, &outsider.pubkey(), &mint_b, &token_program, RECEIVE).await;
+
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let expires_at = now(&mut env).await + 3600;
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    ix.data = escrow::instruction::Make {
+        terms: OfferTerms { taker: Some(taker), ..OfferTerms::default() },
+        ..make_args(&offer, expires_at)
+    }.data();
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let ix = take_by_ix(&env, &outsider.pubkey(), &offer, mint_b, DEPOSIT);
+    let err = send(&mut env.ctx, &[ix], &[&outsider]).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidTaker.into());
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+
+    take(&mut env, &offer, mint_b).await.unwrap();
+    assert_settled(&mut env, &offer).await;
+}
+
+#[tokio::test]
+async fn take_with_wrong_mint_fails() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    let token_program = offer.token_program;
+    let other_mint = create_mint(&mut env, &token_program, &[]).await;
+    let taker = env.taker.pubkey();
+    let taker_other_ata = fund(&mut env, &taker, &other_mint, &token_program, RECEIVE).await;
+
+    let err = take(&mut env, &offer, other_mint).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &taker_other_ata).await, RECEIVE);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_b)).await, RECEIVE);
+    assert!(is_closed(&mut env, &offer.ata(&taker, &offer.mint_a)).await);
+}
+
+#[tokio::test]
+async fn refund_by_non_maker_waits_for_expiry() {
+    let mut env = setup().await;
+    let maker = env.maker.pubkey();
+    let maker_lamports = lamports(&mut env, &maker).await;
+    let (offer, expires_at) = open_offer(&mut env, spl_token::ID, 1).await;
+    // the maker paid the escrow and vault rent
+    assert!(lamports(&mut env, &maker).await < maker_lamports);
+
+    let stranger = Keypair::new();
+    let err = refund_by(&mut env, &stranger, &offer).await.unwrap_err();
+    assert_error(err, EscrowError::OfferNotExpired.into());
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+
+    warp_to(&mut env, expires_at + 1).await;
+    let stranger_lamports = lamports(&mut env, &stranger.pubkey()).await;
+    refund_by(&mut env, &stranger, &offer).await.unwrap();
+
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, DEPOSIT);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    // the escrow and vault rent went back to the maker, not to the cranker
+    assert_eq!(lamports(&mut env, &maker).await, maker_lamports);
+    assert_eq!(lamports(&mut env, &stranger.pubkey()).await, stranger_lamports);
+}
+
+#[tokio::test]
+async fn double_take_fails() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+
+    let err = take(&mut env, &offer, offer.mint_b).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    assert_settled(&mut env, &offer).await;
+}
+
+#[tokio::test]
+async fn take_after_refund_fails() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    refund(&mut env, &offer).await.unwrap();
+
+    let err = take(&mut env, &offer, offer.mint_b).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_b)).await, RECEIVE);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+/// Mints `amount` of mint A straight into the vault, outside the program.
+async fn send_to_vault(env: &mut Env, offer: &Offer, amount: u64) {
+    let payer = env.ctx.payer.pubkey();
+    let ix =
+        spl_token_2022::instruction::mint_to(&offer.token_program, &offer.mint_a, &offer.vault, &payer, &[], amount)
+            .unwrap();
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn take_returns_vault_excess_to_the_recipient() {
+    let mut env = setup().await;
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &token_program, RECEIVE).await;
+    let mut offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    offer.recipient = Pubkey::new_unique();
+    let expires_at = now(&mut env).await + 3600;
+    send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+
+    send_to_vault(&mut env, &offer, 5).await;
+    take(&mut env, &offer, mint_b).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&offer.recipient, &mint_a)).await, 5);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_fails_when_the_vault_is_short() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+
+    // the recorded deposit is no longer backed by the vault
+    let mut account = env.ctx.banks_client.get_account(offer.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.amount = DEPOSIT - 1;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&offer.vault, &account.into());
+
+    let err = take(&mut env, &offer, offer.mint_b).await.unwrap_err();
+    assert_error(err, EscrowError::VaultBalanceMismatch.into());
+}
+
+#[tokio::test]
+async fn make_rejects_zero_amounts() {
+    let mut env = setup().await;
+    let mint_a = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let mint_b = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let maker = env.maker.pubkey();
+    fund(&mut env, &maker, &mint_a, &spl_token::ID, DEPOSIT).await;
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+
+    for (deposit_amount, receive_amount) in [(0, RECEIVE), (DEPOSIT, 0)] {
+        let mut ix = make_ix(&maker, &offer, expires_at);
+        ix.data = escrow::instruction::Make { deposit_amount, receive_amount, ..make_args(&offer, expires_at) }.data();
+        let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+        assert_error(err, EscrowError::InvalidAmount.into());
+    }
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn token_2022_offer_settles() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token_2022::ID, 1).await;
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+    assert_settled(&mut env, &offer).await;
+}
+
+/// Opens an offer whose mint A charges a transfer fee, returning it with the
+/// net amount that reached the vault.
+async fn open_fee_offer(env: &mut Env) -> (Offer, u64) {
+    let token_program = spl_token_2022::ID;
+    let mint_a = create_mint(env, &token_program, &[ExtensionType::TransferFeeConfig]).await;
+    let mint_b = create_mint(env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(env, &taker, &mint_b, &token_program, RECEIVE).await;
+
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let expires_at = now(env).await + 3600;
+    send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+    (offer, DEPOSIT - transfer_fee(DEPOSIT))
+}
+
+fn transfer_fee(amount: u64) -> u64 {
+    amount * TRANSFER_FEE_BPS as u64 / 10_000
+}
+
+async fn withheld(env: &mut Env, token_account: &Pubkey) -> u64 {
+    let account = env.ctx.banks_client.get_account(*token_account).await.unwrap().unwrap();
+    let state = StateWithExtensions::<TokenAccount>::unpack(&account.data).unwrap();
+    u64::from(state.get_extension::<transfer_fee::TransferFeeAmount>().unwrap().withheld_amount)
+}
+
+#[tokio::test]
+async fn token_2022_transfer_fee_records_net_deposit() {
+    let mut env = setup().await;
+    let (offer, net) = open_fee_offer(&mut env).await;
+    assert_eq!(balance(&mut env, &offer.vault).await, net);
+
+    // the offer is for what the vault holds, not what the maker sent
+    let err = take_amount(&mut env, &offer, offer.mint_b, DEPOSIT).await.unwrap_err();
+    assert_error(err, EscrowError::TermsChanged.into());
+    take_amount(&mut env, &offer, offer.mint_b, net).await.unwrap();
+
+    let (maker, taker, treasury) = (env.maker.pubkey(), env.taker.pubkey(), env.treasury);
+    let fee = RECEIVE * FEE_BPS as u64 / 10_000;
+    let taker_ata = offer.ata(&taker, &offer.mint_a);
+    assert_eq!(balance(&mut env, &taker_ata).await, net - transfer_fee(net));
+    assert_eq!(withheld(&mut env, &taker_ata).await, transfer_fee(net));
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_b)).await, RECEIVE - fee);
+    assert_eq!(balance(&mut env, &offer.ata(&treasury, &offer.mint_b)).await, fee);
+    // the vault's withheld fee was harvested to the mint so it could close
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn token_2022_transfer_fee_refund_closes_the_vault() {
+    let mut env = setup().await;
+    let (offer, net) = open_fee_offer(&mut env).await;
+    refund(&mut env, &offer).await.unwrap();
+
+    let maker_ata = offer.ata(&env.maker.pubkey(), &offer.mint_a);
+    assert_eq!(balance(&mut env, &maker_ata).await, net - transfer_fee(net));
+    assert_eq!(withheld(&mut env, &maker_ata).await, transfer_fee(net));
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn token_2022_rejected_extensions() {
+    for extension in [ExtensionType::NonTransferable, ExtensionType::PermanentDelegate] {
+        let mut env = setup().await;
+        let token_program = spl_token_2022::ID;
+        let mint_a = create_mint(&mut env, &token_program, &[extension]).await;
+        let mint_b = create_mint(&mut env, &token_program, &[]).await;
+        let maker = env.maker.pubkey();
+        let maker_ata = fund(&mut env, &maker, &mint_a, &token_program, DEPOSIT).await;
+
+        let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+        let expires_at = now(&mut env).await + 3600;
+        let err = send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker])
+            .await
+            .unwrap_err();
+        assert_error(err, EscrowError::UnsupportedMint.into());
+        assert_eq!(balance(&mut env, &maker_ata).await, DEPOSIT);
+        assert!(is_closed(&mut env, &offer.escrow).await);
+    }
+}
+
+/// Opens an auction whose price falls from `RECEIVE` to half of it over
+/// 1000 seconds, starting now.
+async fn open_auction(env: &mut Env) -> (Offer, DutchAuction) {
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(env, &token_program, &[]).await;
+    let mint_b = create_mint(env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(env, &taker, &mint_b, &token_program, RECEIVE).await;
+
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let start_time = now(env).await;
+    let auction = DutchAuction {
+        end_receive_amount: RECEIVE / 2,
+        start_time,
+        end_time: start_time + 1000,
+    };
+    let ix = make_auction_ix(&maker, &offer, start_time + 3600, Some(auction.clone()));
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    (offer, auction)
+}
+
+#[tokio::test]
+async fn dutch_auction_fills_at_the_decayed_price() {
+    let mut env = setup().await;
+    let (offer, auction) = open_auction(&mut env).await;
+    warp_to(&mut env, auction.start_time + 500).await;
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+
+    let price = RECEIVE - RECEIVE / 4;
+    let fee = price * FEE_BPS as u64 / 10_000;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_b)).await, RECEIVE - price);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_b)).await, price - fee);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn dutch_auction_is_refundable_after_its_window() {
+    let mut env = setup().await;
+    let (offer, auction) = open_auction(&mut env).await;
+    warp_to(&mut env, auction.end_time + 1).await;
+
+    let err = take(&mut env, &offer, offer.mint_b).await.unwrap_err();
+    assert_error(err, EscrowError::OfferExpired.into());
+
+    let stranger = Keypair::new();
+    refund_by(&mut env, &stranger, &offer).await.unwrap();
+    let maker = env.maker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, DEPOSIT);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+/// Opens an arbitrated escrow paying `beneficiary`, with `arbiter` deciding disputes.
+async fn open_arbitrated(env: &mut Env, beneficiary: &Pubkey, arbiter: &Pubkey) -> Offer {
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(env, &token_program, &[]).await;
+    let mint_b = create_mint(env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(env, &taker, &mint_b, &token_program, RECEIVE).await;
+
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let expires_at = now(env).await + 3600;
+    let terms = ArbitrationTerms { beneficiary: *beneficiary, arbiter: *arbiter };
+    let ix = make_with_ix(&maker, &offer, expires_at, None, Some(terms), None);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    offer
+}
+
+fn settle_accounts(env: &Env, authority: &Pubkey, beneficiary: &Pubkey, offer: &Offer) -> Vec<AccountMeta> {
+    escrow::accounts::Settle {
+        authority: *authority,
+        maker: env.maker.pubkey(),
+        recipient: offer.recipient,
+        beneficiary: *beneficiary,
+        mint_a: offer.mint_a,
+        beneficiary_mint_a_ata: offer.ata(beneficiary, &offer.mint_a),
+        recipient_mint_a_ata: offer.ata(&offer.recipient, &offer.mint_a),
+        escrow: offer.escrow,
+        vault: offer.vault,
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: offer.token_program,
+    }
+    .to_account_metas(None)
+}
+
+#[tokio::test]
+async fn arbitrated_escrow_releases_on_mutual_approval() {
+    let mut env = setup().await;
+    let beneficiary = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let arbiter = Pubkey::new_unique();
+    let offer = open_arbitrated(&mut env, &beneficiary.pubkey(), &arbiter).await;
+
+    // nobody can take it, and the maker alone cannot pull it back
+    let err = take(&mut env, &offer, offer.mint_b).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidTaker.into());
+    let err = refund(&mut env, &offer).await.unwrap_err();
+    assert_error(err, EscrowError::OfferNotExpired.into());
+
+    let maker = env.maker.pubkey();
+    let approve = Instruction {
+        program_id: escrow::ID,
+        accounts: settle_accounts(&env, &maker, &beneficiary.pubkey(), &offer),
+        data: escrow::instruction::ApproveRelease {}.data(),
+    };
+    send(&mut env.ctx, &[approve], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+
+    let approve = Instruction {
+        program_id: escrow::ID,
+        accounts: settle_accounts(&env, &beneficiary.pubkey(), &beneficiary.pubkey(), &offer),
+        data: escrow::instruction::ApproveRelease {}.data(),
+    };
+    send(&mut env.ctx, &[approve], &[&beneficiary]).await.unwrap();
+
+    assert_eq!(balance(&mut env, &offer.ata(&beneficiary.pubkey(), &offer.mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, 0);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn arbiter_splits_a_disputed_escrow() {
+    let mut env = setup().await;
+    let beneficiary = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let arbiter = Keypair::new();
+    let offer = open_arbitrated(&mut env, &beneficiary.pubkey(), &arbiter.pubkey()).await;
+    // the arbiter pays for the beneficiary's token account
+    let fund_arbiter = system_instruction::transfer(&env.ctx.payer.pubkey(), &arbiter.pubkey(), LAMPORTS_PER_SOL);
+    send(&mut env.ctx, &[fund_arbiter], &[]).await.unwrap();
+    let resolve = |env: &Env| Instruction {
+        program_id: escrow::ID,
+        accounts: settle_accounts(env, &arbiter.pubkey(), &beneficiary.pubkey(), &offer),
+        data: escrow::instruction::ResolveDispute { beneficiary_amount: DEPOSIT * 3 / 5 }.data(),
+    };
+
+    let ix = resolve(&env);
+    let err = send(&mut env.ctx, &[ix], &[&arbiter]).await.unwrap_err();
+    assert_error(err, EscrowError::NotDisputed.into());
+
+    let dispute = Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::OpenDispute { party: beneficiary.pubkey(), escrow: offer.escrow }
+            .to_account_metas(None),
+        data: escrow::instruction::OpenDispute {}.data(),
+    };
+    send(&mut env.ctx, &[dispute], &[&beneficiary]).await.unwrap();
+    let ix = resolve(&env);
+    send(&mut env.ctx, &[ix], &[&arbiter]).await.unwrap();
+
+    let maker = env.maker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&beneficiary.pubkey(), &offer.mint_a)).await, DEPOSIT * 3 / 5);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, DEPOSIT * 2 / 5);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn milestones_release_in_order_once_approved() {
+    let mut env = setup().await;
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let maker = env.maker.pubkey();
+    fund(&mut env, &maker, &mint_a, &token_program, DEPOSIT).await;
+
+    let beneficiary = env.taker.pubkey();
+    let reviewer = Keypair::new();
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let terms = MilestoneTerms {
+        beneficiary,
+        tranches: vec![
+            Tranche { amount: DEPOSIT / 4, approver: reviewer.pubkey() },
+            Tranche { amount: DEPOSIT - DEPOSIT / 4, approver: maker },
+        ],
+    };
+    let expires_at = now(&mut env).await + 3600;
+    let ix = make_with_ix(&maker, &offer, expires_at, None, None, Some(terms));
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let approve_ix = |approver: Pubkey, index: u8| Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::ApproveMilestone { approver, escrow: offer.escrow }.to_account_metas(None),
+        data: escrow::instruction::ApproveMilestone { index }.data(),
+    };
+    let release_ix = || Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::ReleaseMilestone {
+            payer: maker,
+            maker,
+            recipient: maker,
+            beneficiary,
+            mint_a,
+            beneficiary_mint_a_ata: offer.ata(&beneficiary, &mint_a),
+            escrow: offer.escrow,
+            vault: offer.vault,
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::ReleaseMilestone {}.data(),
+    };
+
+    let err = send(&mut env.ctx, &[release_ix()], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::MilestoneNotApproved.into());
+    let err = send(&mut env.ctx, &[approve_ix(maker, 0)], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::NotApprover.into());
+
+    // the second milestone can be approved early but is only paid after the first
+    send(&mut env.ctx, &[approve_ix(maker, 1)], &[&env.maker]).await.unwrap();
+    let err = send(&mut env.ctx, &[release_ix()], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::MilestoneNotApproved.into());
+
+    send(&mut env.ctx, &[approve_ix(reviewer.pubkey(), 0)], &[&reviewer]).await.unwrap();
+    send(&mut env.ctx, &[release_ix()], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&beneficiary, &mint_a)).await, DEPOSIT / 4);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT - DEPOSIT / 4);
+
+    send(&mut env.ctx, &[release_ix()], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&beneficiary, &mint_a)).await, DEPOSIT);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+}
+
+#[tokio::test]
+async fn milestones_reject_transfer_fee_mints() {
+    let mut env = setup().await;
+    let token_program = spl_token_2022::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[ExtensionType::TransferFeeConfig]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let maker = env.maker.pubkey();
+    let maker_ata = fund(&mut env, &maker, &mint_a, &token_program, DEPOSIT).await;
+
+    // the fee withheld on the deposit would leave the vault short of the tranches
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    let terms = MilestoneTerms {
+        beneficiary: env.taker.pubkey(),
+        tranches: vec![Tranche { amount: DEPOSIT, approver: maker }],
+    };
+    let expires_at = now(&mut env).await + 3600;
+    let ix = make_with_ix(&maker, &offer, expires_at, None, None, Some(terms));
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::UnsupportedMint.into());
+    assert_eq!(balance(&mut env, &maker_ata).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn proceeds_and_refunds_go_to_the_recipient() {
+    let mut env = setup().await;
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &token_program, 3 * DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &token_program, RECEIVE).await;
+    let cold_wallet = Pubkey::new_unique();
+    let expires_at = now(&mut env).await + 3600;
+
+    let mut taken = Offer::new(&maker, 1, mint_a, mint_b, token_program);
+    taken.recipient = cold_wallet;
+    send(&mut env.ctx, &[make_ix(&maker, &taken, expires_at)], &[&env.maker]).await.unwrap();
+    take(&mut env, &taken, mint_b).await.unwrap();
+    let fee = RECEIVE * FEE_BPS as u64 / 10_000;
+    assert_eq!(balance(&mut env, &taken.ata(&cold_wallet, &mint_b)).await, RECEIVE - fee);
+    assert!(is_closed(&mut env, &taken.ata(&maker, &mint_b)).await);
+
+    let mut refunded = Offer::new(&maker, 2, mint_a, mint_b, token_program);
+    refunded.recipient = cold_wallet;
+    send(&mut env.ctx, &[make_ix(&maker, &refunded, expires_at)], &[&env.maker]).await.unwrap();
+    let rent = lamports(&mut env, &refunded.escrow).await + lamports(&mut env, &refunded.vault).await;
+    let before = lamports(&mut env, &cold_wallet).await;
+    refund(&mut env, &refunded).await.unwrap();
+    assert_eq!(balance(&mut env, &refunded.ata(&cold_wallet, &mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &refunded.ata(&maker, &mint_a)).await, DEPOSIT);
+    assert_eq!(lamports(&mut env, &cold_wallet).await - before, rent);
+
+    // the recipient recorded at make is the only one accepted
+    let mut stale = Offer::new(&maker, 3, mint_a, mint_b, token_program);
+    send(&mut env.ctx, &[make_ix(&maker, &stale, expires_at)], &[&env.maker]).await.unwrap();
+    stale.recipient = cold_wallet;
+    let err = refund(&mut env, &stale).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
+
+#[tokio::test]
+async fn refund_many_closes_every_offer() {
+    let mut env = setup().await;
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(&mut env, &token_program, &[]).await;
+    let mint_b = create_mint(&mut env, &token_program, &[]).await;
+    let maker = env.maker.pubkey();
+    let maker_ata = fund(&mut env, &maker, &mint_a, &token_program, 3 * DEPOSIT).await;
+    let expires_at = now(&mut env).await + 3600;
+
+    let mut offers = vec![];
+    for seed in 1..=3 {
+        let offer = Offer::new(&maker, seed, mint_a, mint_b, token_program);
+        send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+        offers.push(offer);
+    }
+    assert_eq!(balance(&mut env, &maker_ata).await, 0);
+
+    let mut accounts = escrow::accounts::RefundMany {
+        maker,
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program,
+    }
+    .to_account_metas(None);
+    for offer in &offers {
+        accounts.extend([
+            AccountMeta::new(offer.escrow, false),
+            AccountMeta::new(offer.vault, false),
+            AccountMeta::new(mint_a, false),
+            AccountMeta::new(maker, false),
+            AccountMeta::new(maker_ata, false),
+        ]);
+    }
+    let ix = Instruction {
+        program_id: escrow::ID,
+        accounts,
+        data: escrow::instruction::RefundMany {}.data(),
+    };
+
+    // the offers are the maker's, so nobody else can batch-refund them
+    let mut foreign = ix.clone();
+    foreign.accounts[0] = AccountMeta::new(env.taker.pubkey(), true);
+    let err = send(&mut env.ctx, &[foreign], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidBatchAccount.into());
+
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &maker_ata).await, 3 * DEPOSIT);
+    for offer in &offers {
+        assert!(is_closed(&mut env, &offer.escrow).await);
+        assert!(is_closed(&mut env, &offer.vault).await);
+    }
+}
+
+#[tokio::test]
+async fn seed_reuse_needs_the_offer_closed() {
+    let mut env = setup().await;
+    let (offer, expires_at) = open_offer(&mut env, spl_token::ID, 7).await;
+    let maker = env.maker.pubkey();
+    let maker_ata = offer.ata(&maker, &offer.mint_a);
+    fund_more(&mut env, &offer, DEPOSIT).await;
+
+    // system program `AccountAlreadyInUse`
+    let err = send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker])
+        .await
+        .unwrap_err();
+    assert_error(err, 0);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &maker_ata).await, DEPOSIT);
+
+    refund(&mut env, &offer).await.unwrap();
+    assert!(is_closed(&mut env, &offer.escrow).await);
+    assert_eq!(balance(&mut env, &maker_ata).await, 2 * DEPOSIT);
+
+    // same seed, same address
+    let reopened = Offer::new(&maker, 7, offer.mint_a, offer.mint_b, offer.token_program);
+    assert_eq!(reopened.escrow, offer.escrow);
+    send(&mut env.ctx, &[make_ix(&maker, &reopened, expires_at)], &[&env.maker])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut env, &reopened.vault).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &maker_ata).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn make_rejects_wrapped_sol_accounts() {
+    let mut env = setup().await;
+    let mint_a = native_mint(&mut env).await;
+    let mint_b = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let (maker, payer) = (env.maker.pubkey(), env.ctx.payer.pubkey());
+    let ix = spl_associated_token_account::instruction::create_associated_token_account(
+        &payer,
+        &maker,
+        &mint_a,
+        &spl_token::ID,
+    );
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+
+    // the native mint is traded as lamports, so a client passing the maker's
+    // WSOL account and a WSOL vault is refused
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    let mut wsol = [offer.ata(&maker, &mint_a), offer.vault].into_iter();
+    for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == escrow::ID) {
+        *meta = AccountMeta::new(wsol.next().unwrap(), false);
+    }
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::WrappedSolNotSupported.into());
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+/// Opens an offer depositing `DEPOSIT` lamports for mint B.
+async fn open_native_offer(env: &mut Env, seed: u64) -> Offer {
+    let mint_a = native_mint(env).await;
+    let mint_b = create_mint(env, &spl_token::ID, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &taker, &mint_b, &spl_token::ID, RECEIVE).await;
+
+    let offer = Offer::new(&maker, seed, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(env).await + 3600;
+    send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+    offer
+}
+
+async fn rent(env: &mut Env, space: usize) -> u64 {
+    env.ctx.banks_client.get_rent().await.unwrap().minimum_balance(space)
+}
+
+#[tokio::test]
+async fn native_deposit_is_refunded_with_the_sol_vault_rent() {
+    let mut env = setup().await;
+    let offer = open_native_offer(&mut env, 1).await;
+    let sol_vault_rent = rent(&mut env, 0).await;
+    assert_eq!(lamports(&mut env, &offer.sol_vault()).await, DEPOSIT + sol_vault_rent);
+
+    let maker = env.maker.pubkey();
+    let maker_lamports = lamports(&mut env, &maker).await;
+    let escrow_lamports = lamports(&mut env, &offer.escrow).await;
+    refund(&mut env, &offer).await.unwrap();
+
+    // the deposit, the SOL vault's rent and the escrow's rent all return
+    assert_eq!(
+        lamports(&mut env, &maker).await,
+        maker_lamports + DEPOSIT + sol_vault_rent + escrow_lamports,
+    );
+    assert!(is_closed(&mut env, &offer.sol_vault()).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn native_deposit_is_taken_with_the_sol_vault_rent() {
+    let mut env = setup().await;
+    let offer = open_native_offer(&mut env, 1).await;
+    let (maker, taker, treasury) = (env.maker.pubkey(), env.taker.pubkey(), env.treasury);
+    let sol_vault_lamports = lamports(&mut env, &offer.sol_vault()).await;
+    let escrow_lamports = lamports(&mut env, &offer.escrow).await;
+    let taker_lamports = lamports(&mut env, &taker).await;
+
+    take(&mut env, &offer, offer.mint_b).await.unwrap();
+
+    // the taker receives the SOL vault and the escrow's rent, and pays for the
+    // recipient's and the treasury's mint B accounts
+    let fee = RECEIVE * FEE_BPS as u64 / 10_000;
+    let ata_rent = rent(&mut env, TokenAccount::LEN).await;
+    assert_eq!(
+        lamports(&mut env, &taker).await,
+        taker_lamports + sol_vault_lamports + escrow_lamports - 2 * ata_rent,
+    );
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_b)).await, RECEIVE - fee);
+    assert_eq!(balance(&mut env, &offer.ata(&treasury, &offer.mint_b)).await, fee);
+    assert!(is_closed(&mut env, &offer.sol_vault()).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn native_payment_is_taken_for_a_token_deposit() {
+    let mut env = setup().await;
+    let mint_a = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let mint_b = native_mint(&mut env).await;
+    let (maker, taker, treasury) = (env.maker.pubkey(), env.taker.pubkey(), env.treasury);
+    fund(&mut env, &maker, &mint_a, &spl_token::ID, DEPOSIT).await;
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+    send(&mut env.ctx, &[make_ix(&maker, &offer, expires_at)], &[&env.maker]).await.unwrap();
+    // a SOL fee only lands on a rent-exempt treasury, as a live treasury wallet is
+    let fund_treasury = system_instruction::transfer(&env.ctx.payer.pubkey(), &treasury, LAMPORTS_PER_SOL);
+    send(&mut env.ctx, &[fund_treasury], &[]).await.unwrap();
+
+    let maker_lamports = lamports(&mut env, &maker).await;
+    let treasury_lamports = lamports(&mut env, &treasury).await;
+    let taker_lamports = lamports(&mut env, &taker).await;
+    let escrow_lamports = lamports(&mut env, &offer.escrow).await;
+    take(&mut env, &offer, mint_b).await.unwrap();
+
+    // the taker's new mint A account costs what the closed vault refunds
+    let fee = RECEIVE * FEE_BPS as u64 / 10_000;
+    assert_eq!(lamports(&mut env, &maker).await, maker_lamports + RECEIVE - fee);
+    assert_eq!(lamports(&mut env, &treasury).await, treasury_lamports + fee);
+    assert_eq!(lamports(&mut env, &taker).await, taker_lamports - RECEIVE + escrow_lamports);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_a)).await, DEPOSIT);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+/// An offer swapping mint A plus `offered` for mint B plus `requested`.
+struct Basket {
+    offer: Offer,
+    offered: BasketLeg,
+    requested: BasketLeg,
+}
+
+impl Basket {
+    fn terms(&self) -> OfferTerms {
+        OfferTerms {
+            offered: vec![self.offered.clone()],
+            requested: vec![self.requested.clone()],
+            ..OfferTerms::default()
+        }
+    }
+
+    fn make_ix(&self, maker: &Pubkey, expires_at: i64) -> Instruction {
+        let offer = &self.offer;
+        let mut ix = make_ix(maker, offer, expires_at);
+        ix.data = escrow::instruction::Make { terms: self.terms(), ..make_args(offer, expires_at) }.data();
+        let mint = self.offered.mint;
+        ix.accounts.extend([
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(offer.ata(maker, &mint), false),
+            AccountMeta::new(offer.ata(&offer.escrow, &mint), false),
+        ]);
+        ix
+    }
+
+    fn take_ix(&self, env: &Env) -> Instruction {
+        let offer = &self.offer;
+        let taker = env.taker.pubkey();
+        let mut ix = take_ix(env, offer, offer.mint_b, DEPOSIT);
+        ix.data = escrow::instruction::Take {
+            amount: DEPOSIT,
+            expected_deposit: DEPOSIT,
+            max_cost: RECEIVE,
+            expected_terms: self.terms(),
+        }
+        .data();
+        let (offered, requested) = (self.offered.mint, self.requested.mint);
+        ix.accounts.extend([
+            AccountMeta::new_readonly(offered, false),
+            AccountMeta::new(offer.ata(&offer.escrow, &offered), false),
+            AccountMeta::new(offer.ata(&taker, &offered), false),
+            AccountMeta::new(offer.ata(&offer.recipient, &offered), false),
+            AccountMeta::new_readonly(requested, false),
+            AccountMeta::new(offer.ata(&taker, &requested), false),
+            AccountMeta::new(offer.ata(&offer.recipient, &requested), false),
+        ]);
+        ix
+    }
+
+    fn refund_ix(&self, env: &Env) -> Instruction {
+        let offer = &self.offer;
+        let mut ix = refund_ix(env, &env.maker.pubkey(), offer);
+        let mint = self.offered.mint;
+        ix.accounts.extend([
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(offer.ata(&offer.escrow, &mint), false),
+            AccountMeta::new(offer.ata(&offer.recipient, &mint), false),
+        ]);
+        ix
+    }
+
+    fn leg_vault(&self) -> Pubkey {
+        self.offer.ata(&self.offer.escrow, &self.offered.mint)
+    }
+}
+
+/// Opens an offer with one extra leg on each side, both funded.
+async fn open_basket(env: &mut Env) -> Basket {
+    let token_program = spl_token::ID;
+    let mint_a = create_mint(env, &token_program, &[]).await;
+    let mint_b = create_mint(env, &token_program, &[]).await;
+    let offered = BasketLeg { mint: create_mint(env, &token_program, &[]).await, amount: 500_000 };
+    let requested = BasketLeg { mint: create_mint(env, &token_program, &[]).await, amount: 700_000 };
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(env, &maker, &mint_a, &token_program, DEPOSIT).await;
+    fund(env, &maker, &offered.mint, &token_program, offered.amount).await;
+    fund(env, &taker, &mint_b, &token_program, RECEIVE).await;
+    fund(env, &taker, &requested.mint, &token_program, requested.amount).await;
+
+    let basket = Basket { offer: Offer::new(&maker, 1, mint_a, mint_b, token_program), offered, requested };
+    let expires_at = now(env).await + 3600;
+    let ix = basket.make_ix(&maker, expires_at);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    basket
+}
+
+#[tokio::test]
+async fn basket_take_settles_every_leg() {
+    let mut env = setup().await;
+    let basket = open_basket(&mut env).await;
+    let (offered, requested) = (basket.offered.clone(), basket.requested.clone());
+    assert_eq!(balance(&mut env, &basket.leg_vault()).await, offered.amount);
+
+    let ix = basket.take_ix(&env);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    let offer = &basket.offer;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    assert_settled(&mut env, offer).await;
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offered.mint)).await, offered.amount);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &requested.mint)).await, requested.amount);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &requested.mint)).await, 0);
+    assert!(is_closed(&mut env, &basket.leg_vault()).await);
+}
+
+#[tokio::test]
+async fn basket_refund_returns_every_leg() {
+    let mut env = setup().await;
+    let basket = open_basket(&mut env).await;
+
+    let ix = basket.refund_ix(&env);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let offer = &basket.offer;
+    let maker = env.maker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &basket.offered.mint)).await, basket.offered.amount);
+    assert!(is_closed(&mut env, &basket.leg_vault()).await);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+fn update_offer_ix(
+    maker: &Pubkey,
+    offer: &Offer,
+    receive_amount: Option<u64>,
+    top_up: u64,
+    withdraw: u64,
+    expires_at: Option<i64>,
+) -> Instruction {
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::UpdateOffer {
+            maker: *maker,
+            mint_a: offer.mint_a,
+            maker_mint_a_ata: offer.token_ata(maker, &offer.mint_a),
+            escrow: offer.escrow,
+            vault: (!offer.native_a()).then_some(offer.vault),
+            sol_vault: offer.native_a().then(|| offer.sol_vault()),
+            system_program: system_program::ID,
+            token_program: offer.token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::UpdateOffer { receive_amount, top_up, withdraw, expires_at }.data(),
+    }
+}
+
+#[tokio::test]
+async fn update_offer_tops_up_withdraws_and_extends() {
+    let mut env = setup().await;
+    let (offer, expires_at) = open_offer(&mut env, spl_token::ID, 1).await;
+    let maker = env.maker.pubkey();
+    fund_more(&mut env, &offer, 500_000).await;
+
+    let ix = update_offer_ix(&maker, &offer, None, 500_000, 0, None);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT + 500_000);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, 0);
+
+    let ix = update_offer_ix(&maker, &offer, None, 0, 300_000, Some(expires_at + 3600));
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT + 200_000);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &offer.mint_a)).await, 300_000);
+
+    // past the original expiry the offer still fills, for the updated deposit
+    warp_to(&mut env, expires_at + 1).await;
+    take_amount(&mut env, &offer, offer.mint_b, DEPOSIT + 200_000).await.unwrap();
+    let taker = env.taker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_a)).await, DEPOSIT + 200_000);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn update_offer_keeps_part_of_the_deposit_and_the_expiry() {
+    let mut env = setup().await;
+    let (offer, expires_at) = open_offer(&mut env, spl_token::ID, 1).await;
+    let maker = env.maker.pubkey();
+
+    let ix = update_offer_ix(&maker, &offer, None, 0, DEPOSIT, None);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidAmount.into());
+
+    let ix = update_offer_ix(&maker, &offer, None, 0, 0, Some(expires_at - 1));
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, EscrowError::InvalidExpiry.into());
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+}
+
+fn claim_vested_ix(env: &Env, offer: &Offer) -> Instruction {
+    let taker = env.taker.pubkey();
+    Instruction {
+        program_id: escrow::ID,
+        accounts: escrow::accounts::ClaimVested {
+            beneficiary: taker,
+            maker: env.maker.pubkey(),
+            recipient: offer.recipient,
+            mint_a: offer.mint_a,
+            beneficiary_mint_a_ata: offer.ata(&taker, &offer.mint_a),
+            recipient_mint_a_ata: Some(offer.ata(&offer.recipient, &offer.mint_a)),
+            escrow: offer.escrow,
+            vault: offer.vault,
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: offer.token_program,
+        }
+        .to_account_metas(None),
+        data: escrow::instruction::ClaimVested {}.data(),
+    }
+}
+
+async fn escrow_state(env: &mut Env, offer: &Offer) -> EscrowState {
+    let account = env.ctx.banks_client.get_account(offer.escrow).await.unwrap().unwrap();
+    EscrowState::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn claim_vested_releases_linearly_after_the_cliff() {
+    let mut env = setup().await;
+    let mint_a = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let mint_b = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &spl_token::ID, DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &spl_token::ID, RECEIVE).await;
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    ix.data = escrow::instruction::Make {
+        terms: OfferTerms { vesting: Some(VestingTerms { cliff: 100, duration: 1_000 }), ..OfferTerms::default() },
+        ..make_args(&offer, expires_at)
+    }
+    .data();
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    // the take pays the maker but keeps mint A in the vault
+    take(&mut env, &offer, mint_b).await.unwrap();
+    let taker_ata = offer.ata(&taker, &mint_a);
+    assert_eq!(balance(&mut env, &taker_ata).await, 0);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+    let start = escrow_state(&mut env, &offer).await.vesting.unwrap().start;
+
+    warp_to(&mut env, start + 50).await;
+    let ix = claim_vested_ix(&env, &offer);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::NothingToClaim.into());
+
+    warp_to(&mut env, start + 400).await;
+    let ix = claim_vested_ix(&env, &offer);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(balance(&mut env, &taker_ata).await, DEPOSIT * 400 / 1_000);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT - DEPOSIT * 400 / 1_000);
+
+    // the final claim empties the vault and closes the offer
+    warp_to(&mut env, start + 1_000).await;
+    let ix = claim_vested_ix(&env, &offer);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(balance(&mut env, &taker_ata).await, DEPOSIT);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn claim_vested_returns_tokens_sent_to_the_vault() {
+    let mut env = setup().await;
+    let mint_a = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let mint_b = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &spl_token::ID, DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &spl_token::ID, RECEIVE).await;
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    ix.data = escrow::instruction::Make {
+        terms: OfferTerms { vesting: Some(VestingTerms { cliff: 0, duration: 1_000 }), ..OfferTerms::default() },
+        ..make_args(&offer, expires_at)
+    }
+    .data();
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    take(&mut env, &offer, mint_b).await.unwrap();
+    let start = escrow_state(&mut env, &offer).await.vesting.unwrap().start;
+
+    warp_to(&mut env, start + 500).await;
+    let ix = claim_vested_ix(&env, &offer);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    // a stray deposit would keep the vault from closing on the final claim
+    send_to_vault(&mut env, &offer, 7).await;
+
+    warp_to(&mut env, start + 1_000).await;
+    let ix = claim_vested_ix(&env, &offer);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&maker, &mint_a)).await, 7);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+/// A take of `amount` by a taker who saw `expected_deposit` left and pays at
+/// most `max_cost`.
+fn fill_ix(env: &Env, offer: &Offer, amount: u64, expected_deposit: u64, max_cost: u64) -> Instruction {
+    let mut ix = take_ix(env, offer, offer.mint_b, amount);
+    ix.data = escrow::instruction::Take {
+        amount,
+        expected_deposit,
+        max_cost,
+        expected_terms: OfferTerms::default(),
+    }
+    .data();
+    ix
+}
+
+#[tokio::test]
+async fn partial_fills_round_the_cost_up() {
+    let mut env = setup().await;
+    let receive = RECEIVE + 1;
+    let mint_a = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let mint_b = create_mint(&mut env, &spl_token::ID, &[]).await;
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    fund(&mut env, &maker, &mint_a, &spl_token::ID, DEPOSIT).await;
+    fund(&mut env, &taker, &mint_b, &spl_token::ID, receive).await;
+    let offer = Offer::new(&maker, 1, mint_a, mint_b, spl_token::ID);
+    let expires_at = now(&mut env).await + 3600;
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    ix.data = escrow::instruction::Make { receive_amount: receive, ..make_args(&offer, expires_at) }.data();
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    // one unit is worth 2.000001 of mint B, the taker pays 3
+    let ix = fill_ix(&env, &offer, 1, DEPOSIT, 3);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_a)).await, 1);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_b)).await, receive - 3);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT - 1);
+
+    // the rest fills at the remaining terms, so the fills add up to the ask
+    let ix = fill_ix(&env, &offer, DEPOSIT - 1, DEPOSIT - 1, receive - 3);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_a)).await, DEPOSIT);
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &mint_b)).await, 0);
+    assert!(is_closed(&mut env, &offer.vault).await);
+    assert!(is_closed(&mut env, &offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_fails_when_the_cost_exceeds_max_cost() {
+    let mut env = setup().await;
+    let (offer, _) = open_offer(&mut env, spl_token::ID, 1).await;
+    let maker = env.maker.pubkey();
+
+    // the maker raises the price after the taker read the offer
+    let ix = update_offer_ix(&maker, &offer, Some(RECEIVE + 1), 0, 0, None);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let ix = fill_ix(&env, &offer, DEPOSIT, DEPOSIT, RECEIVE);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::SlippageExceeded.into());
+    let taker = env.taker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_b)).await, RECEIVE);
+    assert_eq!(balance(&mut env, &offer.vault).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn take_fails_when_the_offer_was_recreated_with_vesting() {
+    let mut env = setup().await;
+    let (offer, expires_at) = open_offer(&mut env, spl_token::ID, 1).await;
+    let maker = env.maker.pubkey();
+    let read = offer_terms(&mut env, &offer).await;
+
+    // same seed, deposit and price, but the taker would wait a year for mint A
+    refund(&mut env, &offer).await.unwrap();
+    let mut ix = make_ix(&maker, &offer, expires_at);
+    let vesting = VestingTerms { cliff: 365 * 86_400, duration: 365 * 86_400 };
+    ix.data = escrow::instruction::Make {
+        terms: OfferTerms { vesting: Some(vesting), ..OfferTerms::default() },
+        ..make_args(&offer, expires_at)
+    }
+    .data();
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let mut ix = take_ix(&env, &offer, offer.mint_b, DEPOSIT);
+    ix.data = escrow::instruction::Take {
+        amount: DEPOSIT,
+        expected_deposit: DEPOSIT,
+        max_cost: RECEIVE,
+        expected_terms: read,
+    }
+    .data();
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::TermsChanged.into());
+    let taker = env.taker.pubkey();
+    assert_eq!(balance(&mut env, &offer.ata(&taker, &offer.mint_b)).await, RECEIVE);
+}
+
+#[tokio::test]
+async fn basket_take_fails_for_other_legs() {
+    let mut env = setup().await;
+    let basket = open_basket(&mut env).await;
+
+    // the taker read a smaller requested leg than the one on the offer
+    let mut read = basket.terms();
+    read.requested[0].amount -= 1;
+    let mut ix = basket.take_ix(&env);
+    ix.data = escrow::instruction::Take {
+        amount: DEPOSIT,
+        expected_deposit: DEPOSIT,
+        max_cost: RECEIVE,
+        expected_terms: read,
+    }
+    .data();
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, EscrowError::TermsChanged.into());
+    assert_eq!(balance(&mut env, &basket.leg_vault()).await, basket.offered.amount);
+}
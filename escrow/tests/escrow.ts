@@ -25,6 +25,7 @@ describe("escrow", () => {
   const seed = new BN(randomBytes(8));
   const receive_amount = 1;
   const deposit_amount = 1;
+  const expires_at = new BN(Math.floor(Date.now() / 1000) + 60 * 60);
 
 
   let [escrow, escrow_bump] = PublicKey.findProgramAddressSync([
@@ -106,7 +107,16 @@ before(
 
   it("lets make an escrow!", async () => {
     // Add your test here.
- await program.methods.make(seed, new BN(receive_amount), new BN(deposit_amount)).accountsPartial({
+ await program.methods.make(seed, new BN(receive_amount), new BN(deposit_amount), expires_at, {
+        taker: null,
+        offered: [],
+        requested: [],
+        vesting: null,
+        auction: null,
+        arbitration: null,
+        milestones: null,
+        recipient: null,
+      }).accountsPartial({
         maker: maker.publicKey,
         mintA,
         mintB,
@@ -12,5 +12,27 @@ pub enum MarketplaceError {
     InvalidCollection,
     #[msg("Collection not set")]
     CollectionNotSet,
+    #[msg("Fee can be at most 10,000 basis points")]
+    InvalidFee,
+    #[msg("Treasury cannot cover the withdrawal and stay rent exempt")]
+    InsufficientTreasury,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+    #[msg("Creator accounts do not match the verified creators in the metadata")]
+    InvalidCreator,
+    #[msg("Bid price must be greater than zero")]
+    InvalidBidPrice,
+    #[msg("The NFT does not match the bid")]
+    BidMismatch,
+    #[msg("The NFT is listed by someone else")]
+    InvalidListing,
+    #[msg("A rewards token account is required while its reward rate is non-zero")]
+    MissingRewardsAccount,
+    #[msg("Listing price must be greater than zero")]
+    InvalidPrice,
+    #[msg("The listing price changed since it was read")]
+    PriceMismatch,
+    #[msg("The royalty and marketplace fee add up to more than the price")]
+    RoyaltyTooHigh,
 }
 
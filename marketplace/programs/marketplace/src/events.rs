@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::state::BidTarget;
+
+#[event]
+pub struct ListingPriceUpdated {
+    pub listing: Pubkey,
+    pub maker: Pubkey,
+    pub mint: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+}
+
+#[event]
+pub struct BidPlaced {
+    pub bid: Pubkey,
+    pub bidder: Pubkey,
+    pub target: BidTarget,
+    pub price: u64,
+}
+
+#[event]
+pub struct BidCancelled {
+    pub bid: Pubkey,
+    pub bidder: Pubkey,
+}
+
+#[event]
+pub struct BidAccepted {
+    pub bid: Pubkey,
+    pub bidder: Pubkey,
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    pub price: u64,
+}
@@ -0,0 +1,68 @@
+use anchor_lang::prelude::*;
+use anchor_spl::metadata::MetadataAccount;
+
+use crate::errors::MarketplaceError;
+use crate::state::{Marketplace, MAX_FEE_BPS};
+
+/// How the price of a sale is paid out.
+pub struct SaleSplit<'a, 'info> {
+    /// The marketplace fee, for the treasury.
+    pub fee: u64,
+    /// Each verified creator's wallet and their share of the royalty.
+    pub royalties: Vec<(&'a AccountInfo<'info>, u64)>,
+    /// What is left for the seller.
+    pub proceeds: u64,
+}
+
+/// Fails if the NFT's royalty and the marketplace fee add up to more than
+/// the whole price, which would leave nothing to pay the seller with.
+pub fn check_royalty(marketplace: &Marketplace, metadata: &MetadataAccount) -> Result<()> {
+    require!(
+        marketplace.fee as u32 + metadata.seller_fee_basis_points as u32 <= MAX_FEE_BPS as u32,
+        MarketplaceError::RoyaltyTooHigh
+    );
+    Ok(())
+}
+
+/// Splits a sale at `price` between the treasury, the NFT's verified creators
+/// and the seller. `creators` are the verified creators' wallets in metadata
+/// order; unverified creators are skipped and their share stays with the
+/// seller.
+pub fn split_sale<'a, 'info>(
+    marketplace: &Marketplace,
+    metadata: &MetadataAccount,
+    price: u64,
+    creators: &'a [AccountInfo<'info>],
+) -> Result<SaleSplit<'a, 'info>> {
+    // metadata stays mutable, so the royalty may have been raised since listing
+    check_royalty(marketplace, metadata)?;
+    let fee = marketplace.fee_for(price).ok_or(MarketplaceError::Overflow)?;
+    let royalty = royalty_for(price, metadata.seller_fee_basis_points)?;
+
+    let verified: Vec<_> = metadata
+        .creators
+        .iter()
+        .flatten()
+        .filter(|creator| creator.verified)
+        .collect();
+    require_eq!(creators.len(), verified.len(), MarketplaceError::InvalidCreator);
+
+    let mut paid = fee;
+    let mut royalties = Vec::with_capacity(creators.len());
+    for (creator, wallet) in verified.iter().zip(creators) {
+        require_keys_eq!(creator.address, wallet.key(), MarketplaceError::InvalidCreator);
+        // at most `royalty`, since shares add up to 100
+        let share = (royalty as u128 * creator.share as u128 / 100) as u64;
+        royalties.push((wallet, share));
+        paid = paid.checked_add(share).ok_or(MarketplaceError::Overflow)?;
+    }
+
+    let proceeds = price.checked_sub(paid).ok_or(MarketplaceError::Overflow)?;
+    Ok(SaleSplit { fee, royalties, proceeds })
+}
+
+/// The royalty owed to creators on a sale at `price`, rounded down.
+fn royalty_for(price: u64, seller_fee_basis_points: u16) -> Result<u64> {
+    let royalty = price as u128 * seller_fee_basis_points as u128 / MAX_FEE_BPS as u128;
+    u64::try_from(royalty).map_err(|_| error!(MarketplaceError::Overflow))
+}
@@ -0,0 +1,187 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    metadata::{MasterEditionAccount, Metadata, MetadataAccount},
+    token_interface::{close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::errors::MarketplaceError;
+use crate::events::BidAccepted;
+use crate::helpers::split_sale;
+use crate::state::{Bid, BidTarget, Listing, Marketplace};
+
+#[derive(Accounts)]
+pub struct AcceptBid<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    /// Gets the bid account's rent back.
+    #[account(mut)]
+    pub bidder: SystemAccount<'info>,
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+    #[account(
+        mut,
+        seeds = [b"treasury", marketplace.key().as_ref()],
+        bump = marketplace.treasury_bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = bidder,
+        has_one = bidder,
+        seeds = [
+            b"bid",
+            marketplace.key().as_ref(),
+            bidder.key().as_ref(),
+            bid.target.tag().as_ref(),
+            bid.target.key().as_ref()
+        ],
+        bump = bid.bump,
+    )]
+    pub bid: Box<Account<'info, Bid>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+    )]
+    pub seller_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = mint,
+        associated_token::authority = bidder,
+    )]
+    pub bidder_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Passed with `vault` when the NFT is listed; the listing is closed and
+    /// the NFT comes out of its vault.
+    #[account(
+        mut,
+        close = seller,
+        constraint = listing.maker == seller.key() @ MarketplaceError::InvalidListing,
+        seeds = [marketplace.key().as_ref(), mint.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Option<Box<Account<'info, Listing>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
+        bump,
+        seeds::program = metadata_program.key(),
+    )]
+    pub metadata: Box<Account<'info, MetadataAccount>>,
+    #[account(
+        seeds = [
+            b"metadata",
+            metadata_program.key().as_ref(),
+            mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub master_edition: Box<Account<'info, MasterEditionAccount>>,
+    pub metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptBid<'info> {
+    /// `price` is the bid the seller agreed to, so a bid cancelled and placed
+    /// again lower in the meantime is not accepted.
+    pub fn check_bid(&self, price: u64) -> Result<()> {
+        require_eq!(self.bid.price, price, MarketplaceError::BidMismatch);
+        match self.bid.target {
+            BidTarget::Mint(mint) => require_keys_eq!(mint, self.mint.key(), MarketplaceError::BidMismatch),
+            BidTarget::Collection(collection) => require!(
+                self.metadata
+                    .collection
+                    .as_ref()
+                    .is_some_and(|c| c.verified && c.key == collection),
+                MarketplaceError::BidMismatch
+            ),
+        }
+        Ok(())
+    }
+
+    /// Pays the bid out of its escrow: the marketplace fee to the treasury,
+    /// the royalty to the verified creators in `creators`, and the rest to the
+    /// seller. The lamports move directly, so this runs after `send_nft`: the
+    /// runtime rejects a CPI once an account's balance was edited by hand.
+    pub fn send_sol(&self, creators: &[AccountInfo<'info>]) -> Result<()> {
+        let split = split_sale(&self.marketplace, &self.metadata, self.bid.price, creators)?;
+        for (wallet, share) in split.royalties {
+            self.pay(wallet, share)?;
+        }
+        self.pay(&self.treasury.to_account_info(), split.fee)?;
+        self.pay(&self.seller.to_account_info(), split.proceeds)
+    }
+
+    fn pay(&self, to: &AccountInfo<'info>, lamports: u64) -> Result<()> {
+        self.bid.to_account_info().sub_lamports(lamports)?;
+        to.add_lamports(lamports)?;
+        Ok(())
+    }
+
+    pub fn send_nft(&mut self) -> Result<()> {
+        match (&self.listing, &self.vault) {
+            (Some(listing), Some(vault)) => {
+                let seeds = &[
+                    &self.marketplace.key().to_bytes()[..],
+                    &self.mint.key().to_bytes()[..],
+                    &[listing.bump],
+                ];
+                let signer_seeds = &[&seeds[..]];
+
+                let accounts = TransferChecked {
+                    from: vault.to_account_info(),
+                    to: self.bidder_ata.to_account_info(),
+                    authority: listing.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                };
+                let cpi_ctx =
+                    CpiContext::new_with_signer(self.token_program.to_account_info(), accounts, signer_seeds);
+                transfer_checked(cpi_ctx, 1, self.mint.decimals)?;
+
+                let accounts = CloseAccount {
+                    account: vault.to_account_info(),
+                    destination: self.seller.to_account_info(),
+                    authority: listing.to_account_info(),
+                };
+                let cpi_ctx =
+                    CpiContext::new_with_signer(self.token_program.to_account_info(), accounts, signer_seeds);
+                close_account(cpi_ctx)
+            }
+            (None, None) => {
+                let accounts = TransferChecked {
+                    from: self.seller_ata.to_account_info(),
+                    to: self.bidder_ata.to_account_info(),
+                    authority: self.seller.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), accounts);
+                transfer_checked(cpi_ctx, 1, self.mint.decimals)
+            }
+            _ => err!(MarketplaceError::InvalidListing),
+        }
+    }
+
+    pub fn emit_accepted(&self) {
+        emit!(BidAccepted {
+            bid: self.bid.key(),
+            bidder: self.bidder.key(),
+            seller: self.seller.key(),
+            mint: self.mint.key(),
+            price: self.bid.price,
+        });
+    }
+}
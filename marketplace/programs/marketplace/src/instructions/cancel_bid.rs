@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::events::BidCancelled;
+use crate::state::{Bid, Marketplace};
+
+#[derive(Accounts)]
+pub struct CancelBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    /// Closing the bid returns the escrowed lamports along with its rent.
+    #[account(
+        mut,
+        close = bidder,
+        has_one = bidder,
+        seeds = [
+            b"bid",
+            marketplace.key().as_ref(),
+            bidder.key().as_ref(),
+            bid.target.tag().as_ref(),
+            bid.target.key().as_ref()
+        ],
+        bump = bid.bump,
+    )]
+    pub bid: Account<'info, Bid>,
+}
+
+impl<'info> CancelBid<'info> {
+    pub fn cancel_bid(&mut self) -> Result<()> {
+        emit!(BidCancelled {
+            bid: self.bid.key(),
+            bidder: self.bidder.key(),
+        });
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
-
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
+use anchor_spl::token_interface::{
+    close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 use crate::state::{Listing, Marketplace};
 
 #[derive(Accounts)]
@@ -8,7 +9,7 @@ pub struct Delist<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes()],
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
         bump = marketplace.bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
@@ -22,6 +23,7 @@ pub struct Delist<'info> {
     #[account(
         mut,
         close = maker,
+        has_one = maker,
         seeds = [marketplace.key().as_ref(), maker_mint.key().as_ref()],
         bump = listing.bump,
     )]
@@ -31,8 +33,8 @@ pub struct Delist<'info> {
         associated_token::mint = maker_mint,
         associated_token::authority = listing,
     )]
-    pub vault: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, TokenInterface>,
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -60,4 +62,27 @@ impl<'info> Delist<'info> {
 
         transfer_checked(cpi_ctx, 1, self.maker_mint.decimals)
     }
+
+    pub fn close_vault(&mut self) -> Result<()> {
+        let seeds = &[
+            &self.marketplace.key().to_bytes()[..],
+            &self.maker_mint.key().to_bytes()[..],
+            &[self.listing.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.listing.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            accounts,
+            signer_seeds,
+        );
+
+        close_account(cpi_ctx)
+    }
 }
@@ -1,9 +1,8 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
 use anchor_spl::token_interface::{Mint, TokenInterface};
 
 use crate::errors::MarketplaceError;
-use crate::Marketplace;
-
+use crate::state::{Marketplace, MAX_FEE_BPS, MAX_NAME_LEN};
 
 #[derive(Accounts)]
 #[instruction(name: String)]
@@ -14,11 +13,12 @@ pub struct Initialize<'info> {
         init,
         payer = admin,
         space = Marketplace::INIT_SPACE,
-        seeds = [b"marketplace", name.as_str().as_bytes()],
+        seeds = [b"marketplace", name.as_bytes()],
         bump
     )]
     pub marketplace: Account<'info, Marketplace>,
     #[account(
+        mut,
         seeds = [b"treasury", marketplace.key().as_ref()],
         bump,
     )]
@@ -32,25 +32,44 @@ pub struct Initialize<'info> {
         mint::authority = marketplace
     )]
     pub rewards: InterfaceAccount<'info, Mint>,
-    token_program: Interface<'info, TokenInterface>,
-    system_program: Program<'info, System>
-
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
 }
 
 impl<'info> Initialize<'info> {
     pub fn init(&mut self, name: String, fee: u16, bumps: &InitializeBumps) -> Result<()> {
-        require!(name.len() > 0 && name.len() < 4 + 33, MarketplaceError::NameTooLong);
-        
+        require!(!name.is_empty(), MarketplaceError::InvalidName);
+        require!(name.len() <= MAX_NAME_LEN, MarketplaceError::NameTooLong);
+        require!(fee <= MAX_FEE_BPS, MarketplaceError::InvalidFee);
+
         self.marketplace.set_inner(Marketplace {
             admin: self.admin.key(),
             fee,
             bump: bumps.marketplace,
             treasury_bump: bumps.treasury,
             rewards_bump: bumps.rewards,
+            taker_reward_rate: 0,
+            maker_reward_rate: 0,
             name,
         });
 
         Ok(())
     }
+
+    /// Funds the treasury's rent up front, so fees smaller than the rent
+    /// exempt minimum can still be paid into it.
+    pub fn fund_treasury(&mut self) -> Result<()> {
+        let rent = Rent::get()?.minimum_balance(0).saturating_sub(self.treasury.lamports());
+        if rent == 0 {
+            return Ok(());
+        }
+
+        let cpi_accounts = Transfer {
+            from: self.admin.to_account_info(),
+            to: self.treasury.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        transfer(cpi_ctx, rent)
+    }
 }
 
@@ -1,34 +1,37 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    metadata::{Metadata, MetadataAccount},
-    token::{Mint, Token, TokenAccount, Transfer as SplTransfer, transfer as slp_transfer, }
+    metadata::{MasterEditionAccount, Metadata, MetadataAccount},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
-use create::marketplace::Listing;
+use crate::errors::MarketplaceError;
+use crate::helpers::check_royalty;
+use crate::state::{Listing, Marketplace};
 
+#[derive(Accounts)]
 pub struct List<'info> {
-    #[accounts(mut)]
+    #[account(mut)]
     pub maker: Signer<'info>,
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes()],
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
         bump = marketplace.bump,
     )]
-    pub marketplace: Account<'info, Marketplace>,
-    pub maker_mint: Account<'info, Mint>,
+    pub marketplace: Box<Account<'info, Marketplace>>,
+    pub maker_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
         associated_token::mint = maker_mint,
         associated_token::authority = maker,
     )]
-    pub maker_ata: Account<'info, TokenAccount>,
+    pub maker_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
         payer = maker,
         associated_token::mint = maker_mint,
         associated_token::authority = listing,
     )]
-    pub vault: Account<'info, TokenAccount>,
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
         payer = maker,
@@ -36,19 +39,21 @@ pub struct List<'info> {
         bump,
         space = Listing::INIT_SPACE,
     )]
-    pub listing: Account<'info, Listing>,
-    pub collection_mint: Account<'info, Mint>,
+    pub listing: Box<Account<'info, Listing>>,
+    pub collection_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         seeds = [b"metadata", metadata_program.key().as_ref(), maker_mint.key().as_ref()],
         bump,
         seeds::program = metadata_program.key(),
-        constraint = metadata.collection.as_ref().unwrap().key.as_ref() == collection_mint.key().as_ref(),
-        constraint = metadata.collection.as_ref().unwrap().verified == true,
-       )]
-    pub metadata: Account<'info, MetadataAccount>,
+        constraint = metadata.collection.as_ref().is_some_and(|c| c.key == collection_mint.key())
+            @ MarketplaceError::InvalidCollection,
+        constraint = metadata.collection.as_ref().is_some_and(|c| c.verified)
+            @ MarketplaceError::CollectionNotSet,
+    )]
+    pub metadata: Box<Account<'info, MetadataAccount>>,
     #[account(
         seeds = [
-            b"metadata", 
+            b"metadata",
             metadata_program.key().as_ref(),
             maker_mint.key().as_ref(),
             b"edition"
@@ -56,7 +61,7 @@ pub struct List<'info> {
         seeds::program = metadata_program.key(),
         bump,
     )]
-    pub master_edition: Account<'info, MasterEditionAccount>,
+    pub master_edition: Box<Account<'info, MasterEditionAccount>>,
     pub metadata_program: Program<'info, Metadata>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -65,6 +70,8 @@ pub struct List<'info> {
 
 impl<'info> List<'info> {
     pub fn create_listing(&mut self, price: u64, bumps: &ListBumps) -> Result<()> {
+        require!(price > 0, MarketplaceError::InvalidPrice);
+        check_royalty(&self.marketplace, &self.metadata)?;
         self.listing.set_inner(Listing {
             maker: self.maker.key(),
             mint: self.maker_mint.key(),
@@ -91,4 +98,4 @@ impl<'info> List<'info> {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -2,9 +2,21 @@ pub mod initialize;
 pub mod list;
 pub mod delist;
 pub mod purchase;
+pub mod withdraw_treasury;
+pub mod set_reward_rates;
+pub mod update_price;
+pub mod place_bid;
+pub mod cancel_bid;
+pub mod accept_bid;
 
 
 pub use initialize::*;
 pub use list::*;
 pub use delist::*;
 pub use purchase::*;
+pub use withdraw_treasury::*;
+pub use set_reward_rates::*;
+pub use update_price::*;
+pub use place_bid::*;
+pub use cancel_bid::*;
+pub use accept_bid::*;
@@ -0,0 +1,62 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+
+use crate::errors::MarketplaceError;
+use crate::events::BidPlaced;
+use crate::state::{Bid, BidTarget, Marketplace};
+
+#[derive(Accounts)]
+#[instruction(target: BidTarget)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = bidder,
+        seeds = [
+            b"bid",
+            marketplace.key().as_ref(),
+            bidder.key().as_ref(),
+            target.tag().as_ref(),
+            target.key().as_ref()
+        ],
+        bump,
+        space = Bid::INIT_SPACE,
+    )]
+    pub bid: Account<'info, Bid>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PlaceBid<'info> {
+    /// Escrows `price` lamports in the bid account until the bid is accepted
+    /// or cancelled.
+    pub fn place_bid(&mut self, target: BidTarget, price: u64, bumps: &PlaceBidBumps) -> Result<()> {
+        require!(price > 0, MarketplaceError::InvalidBidPrice);
+
+        self.bid.set_inner(Bid {
+            bidder: self.bidder.key(),
+            target,
+            price,
+            bump: bumps.bid,
+        });
+
+        let cpi_accounts = Transfer {
+            from: self.bidder.to_account_info(),
+            to: self.bid.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        transfer(cpi_ctx, price)?;
+
+        emit!(BidPlaced {
+            bid: self.bid.key(),
+            bidder: self.bidder.key(),
+            target,
+            price,
+        });
+        Ok(())
+    }
+}
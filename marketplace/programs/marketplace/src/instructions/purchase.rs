@@ -1,7 +1,9 @@
 
 use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, transfer_checked, TransferChecked, CloseAccount, close_account}};
+use anchor_spl::{associated_token::AssociatedToken, metadata::{Metadata, MetadataAccount}, token_interface::{Mint, TokenAccount, TokenInterface, transfer_checked, TransferChecked, CloseAccount, close_account, mint_to, MintTo}};
 
+use crate::errors::MarketplaceError;
+use crate::helpers::split_sale;
 use crate::state::{Listing, Marketplace};
 
 #[derive(Accounts)]
@@ -12,7 +14,7 @@ pub struct Purchase<'info> {
     pub maker: SystemAccount<'info>,
     pub maker_mint: InterfaceAccount<'info, Mint>,
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes()],
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
         bump = marketplace.bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
@@ -33,12 +35,22 @@ pub struct Purchase<'info> {
         mut,
         seeds = [marketplace.key().as_ref(), maker_mint.key().as_ref()],
         bump = listing.bump,
+        has_one = maker,
         close = maker,
     )]
     pub listing: Account<'info, Listing>,
+    /// Purchases of NFTs without metadata are refused; royalties are read
+    /// from it.
     #[account(
-        seeds = [b"treasury", marketplace.key().as_ref()],
+        seeds = [b"metadata", metadata_program.key().as_ref(), maker_mint.key().as_ref()],
         bump,
+        seeds::program = metadata_program.key(),
+    )]
+    pub metadata: Box<Account<'info, MetadataAccount>>,
+    #[account(
+        mut,
+        seeds = [b"treasury", marketplace.key().as_ref()],
+        bump = marketplace.treasury_bump,
     )]
     pub treasury: SystemAccount<'info>,
     #[account(
@@ -48,29 +60,60 @@ pub struct Purchase<'info> {
         mint::decimals = 6,
         mint::authority = marketplace,
     )]
-    pub rewards_mint: InterfaceAccount<'info, Mint>,
+    pub rewards_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Required only while the taker reward rate is non-zero.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = rewards_mint,
+        associated_token::authority = taker,
+    )]
+    pub taker_rewards_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Required only while the maker reward rate is non-zero. Created at the
+    /// taker's expense if the maker has never earned rewards.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = rewards_mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_rewards_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub metadata_program: Program<'info, Metadata>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Purchase<'info> {
-    pub fn send_sol(&self) -> Result<()> {
-        let cpi_program = self.system_program.to_account_info();
-        
+    /// `price` is the listing price the taker agreed to, so a listing repriced
+    /// in the meantime is not bought at the new price.
+    pub fn check_price(&self, price: u64) -> Result<()> {
+        require_eq!(self.listing.price, price, MarketplaceError::PriceMismatch);
+        Ok(())
+    }
+
+    /// Pays the marketplace fee to the treasury, each verified creator their
+    /// share of the royalty, and the maker the rest. `creators` are the
+    /// verified creators' wallets in metadata order.
+    pub fn send_sol(&self, creators: &[AccountInfo<'info>]) -> Result<()> {
+        let split = split_sale(&self.marketplace, &self.metadata, self.listing.price, creators)?;
+        for (wallet, share) in split.royalties {
+            self.pay(wallet, share)?;
+        }
+        self.pay(&self.treasury.to_account_info(), split.fee)?;
+        self.pay(&self.maker.to_account_info(), split.proceeds)
+    }
+
+    fn pay(&self, to: &AccountInfo<'info>, lamports: u64) -> Result<()> {
+        if lamports == 0 {
+            return Ok(());
+        }
         let cpi_accounts = Transfer {
             from: self.taker.to_account_info(),
-            to: self.maker.to_account_info(),
+            to: to.clone(),
         };
-
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-
-        let amount = self.listing.price
-            .checked_sub(self.marketplace.fee as u64).unwrap();
-
-        transfer(cpi_ctx, self.listing.price - amount)?;
-
-        Ok(())
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        transfer(cpi_ctx, lamports)
     }
 
     pub fn send_nft(&mut self) -> Result<()> {
@@ -121,4 +164,40 @@ impl<'info> Purchase<'info> {
 
         close_account(cpi_ctx)
     }
+
+    /// Mints loyalty rewards to both sides of the sale at the marketplace's
+    /// current rates. A side whose rate is zero needs no rewards account.
+    pub fn mint_rewards(&mut self) -> Result<()> {
+        self.mint_reward(self.taker_rewards_ata.as_deref(), self.marketplace.taker_reward_rate)?;
+        self.mint_reward(self.maker_rewards_ata.as_deref(), self.marketplace.maker_reward_rate)
+    }
+
+    fn mint_reward(&self, to: Option<&InterfaceAccount<'info, TokenAccount>>, rate: u64) -> Result<()> {
+        if rate == 0 {
+            return Ok(());
+        }
+        let to = to.ok_or(MarketplaceError::MissingRewardsAccount)?.to_account_info();
+        let amount = Marketplace::rewards_for(self.listing.price, rate).ok_or(MarketplaceError::Overflow)?;
+        if amount == 0 {
+            return Ok(());
+        }
+        let seeds: &[&[u8]] = &[
+            b"marketplace",
+            self.marketplace.name.as_bytes(),
+            &[self.marketplace.bump],
+        ];
+        let signer_seeds = &[seeds];
+
+        let accounts = MintTo {
+            mint: self.rewards_mint.to_account_info(),
+            to,
+            authority: self.marketplace.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            accounts,
+            signer_seeds,
+        );
+        mint_to(cpi_ctx, amount)
+    }
 }
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Marketplace;
+
+#[derive(Accounts)]
+pub struct SetRewardRates<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = admin,
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+}
+
+impl<'info> SetRewardRates<'info> {
+    /// Rates are in rewards base units per SOL of price; zero turns rewards
+    /// off for that side of the sale.
+    pub fn set_reward_rates(&mut self, taker_rate: u64, maker_rate: u64) -> Result<()> {
+        self.marketplace.taker_reward_rate = taker_rate;
+        self.marketplace.maker_reward_rate = maker_rate;
+        Ok(())
+    }
+}
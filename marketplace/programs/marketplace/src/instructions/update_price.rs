@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::MarketplaceError;
+use crate::events::ListingPriceUpdated;
+use crate::state::{Listing, Marketplace};
+
+#[derive(Accounts)]
+pub struct UpdatePrice<'info> {
+    pub maker: Signer<'info>,
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        has_one = maker,
+        seeds = [marketplace.key().as_ref(), listing.mint.as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+impl<'info> UpdatePrice<'info> {
+    /// Reprices the listing in place; the NFT stays in its vault.
+    pub fn update_price(&mut self, price: u64) -> Result<()> {
+        require!(price > 0, MarketplaceError::InvalidPrice);
+        let old_price = self.listing.price;
+        self.listing.price = price;
+
+        emit!(ListingPriceUpdated {
+            listing: self.listing.key(),
+            maker: self.maker.key(),
+            mint: self.listing.mint,
+            old_price,
+            new_price: price,
+        });
+        Ok(())
+    }
+}
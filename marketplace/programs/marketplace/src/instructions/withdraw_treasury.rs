@@ -0,0 +1,46 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+
+use crate::errors::MarketplaceError;
+use crate::state::Marketplace;
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin,
+        seeds = [b"marketplace", marketplace.name.as_bytes()],
+        bump = marketplace.bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"treasury", marketplace.key().as_ref()],
+        bump = marketplace.treasury_bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawTreasury<'info> {
+    /// Sends `amount` of collected fees to the admin. The treasury keeps its
+    /// rent exempt minimum.
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        let available = self
+            .treasury
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        require!(amount <= available, MarketplaceError::InsufficientTreasury);
+
+        let marketplace = self.marketplace.key();
+        let seeds = &[b"treasury", marketplace.as_ref(), &[self.marketplace.treasury_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: self.treasury.to_account_info(),
+            to: self.admin.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.system_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)
+    }
+}
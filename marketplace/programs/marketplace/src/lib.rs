@@ -3,6 +3,8 @@ use anchor_lang::prelude::*;
 pub mod state;
 pub mod instructions;
 pub mod errors;
+pub mod events;
+pub mod helpers;
 
 pub use state::*;
 pub use instructions::*;
@@ -12,34 +14,62 @@ declare_id!("2x2hWcMfrsfkRbh3aXWFV5BEoa4p6e2uUamVnkHEt8Wx");
 
 #[program]
 pub mod marketplace {
-   
-
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>, name: String, fee: u16) -> Result<()> {
         ctx.accounts.init(name, fee, &ctx.bumps)?;
+        ctx.accounts.fund_treasury()?;
         Ok(())
     }
 
     pub fn listing(ctx: Context<List>, price: u64) -> Result<()> {
-        ctx.accounts.createlisting(price, &ctx.bumps)?;
+        ctx.accounts.create_listing(price, &ctx.bumps)?;
         ctx.accounts.deposit_nft()?;
         Ok(())
     }
 
+    pub fn update_price(ctx: Context<UpdatePrice>, price: u64) -> Result<()> {
+        ctx.accounts.update_price(price)
+    }
+
     pub fn delist(ctx: Context<Delist>) -> Result<()> {
         ctx.accounts.withdraw_nft()?;
+        ctx.accounts.close_vault()?;
         Ok(())
     }
 
-    pub fn purchase(ctx: Context<Purchase>) -> Result<()> {
-        ctx.accounts.send_sol()?;
+    pub fn purchase<'info>(ctx: Context<'_, '_, 'info, 'info, Purchase<'info>>, price: u64) -> Result<()> {
+        ctx.accounts.check_price(price)?;
+        ctx.accounts.send_sol(ctx.remaining_accounts)?;
         ctx.accounts.send_nft()?;
         ctx.accounts.close_mint_vault()?;
+        ctx.accounts.mint_rewards()?;
 
         Ok(())
     }
+
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw(amount)
+    }
+
+    pub fn place_bid(ctx: Context<PlaceBid>, target: BidTarget, price: u64) -> Result<()> {
+        ctx.accounts.place_bid(target, price, &ctx.bumps)
+    }
+
+    pub fn cancel_bid(ctx: Context<CancelBid>) -> Result<()> {
+        ctx.accounts.cancel_bid()
+    }
+
+    pub fn accept_bid<'info>(ctx: Context<'_, '_, 'info, 'info, AcceptBid<'info>>, price: u64) -> Result<()> {
+        ctx.accounts.check_bid(price)?;
+        ctx.accounts.send_nft()?;
+        ctx.accounts.send_sol(ctx.remaining_accounts)?;
+        ctx.accounts.emit_accepted();
+        Ok(())
+    }
+
+    pub fn set_reward_rates(ctx: Context<SetRewardRates>, taker_rate: u64, maker_rate: u64) -> Result<()> {
+        ctx.accounts.set_reward_rates(taker_rate, maker_rate)
+    }
 }
 
-#[derive(Accounts)]
-pub struct Initialize {}
@@ -1,16 +1,44 @@
 use anchor_lang::prelude::*;
 
+/// Longest marketplace name, in bytes; the name is also a PDA seed.
+pub const MAX_NAME_LEN: usize = 32;
+
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Reward rates are in base units of the rewards mint per SOL of price.
+pub const REWARD_RATE_PRICE_UNIT: u64 = 1_000_000_000;
+
+#[account]
 pub struct Marketplace {
     pub admin: Pubkey,
+    /// Cut of every sale kept in the treasury, in basis points.
     pub fee: u16,
     pub bump: u8,
     pub treasury_bump: u8,
     pub rewards_bump: u8,
+    /// Rewards minted to the buyer on each purchase.
+    pub taker_reward_rate: u64,
+    /// Rewards minted to the seller on each purchase.
+    pub maker_reward_rate: u64,
     pub name: String,
 }
 
 impl Space for Marketplace {
-    const INIT_SPACE: usize = 8 + 32 + 2 + 1 + 1 + 4 + 32;
+    const INIT_SPACE: usize = 8 + 32 + 2 + 1 + 1 + 1 + 8 + 8 + 4 + MAX_NAME_LEN;
+}
+
+impl Marketplace {
+    /// The marketplace's cut of a sale at `price`, rounded down.
+    pub fn fee_for(&self, price: u64) -> Option<u64> {
+        let fee = (price as u128).checked_mul(self.fee as u128)? / MAX_FEE_BPS as u128;
+        u64::try_from(fee).ok()
+    }
+
+    /// Rewards earned at `rate` on a sale at `price`, rounded down.
+    pub fn rewards_for(price: u64, rate: u64) -> Option<u64> {
+        let rewards = price as u128 * rate as u128 / REWARD_RATE_PRICE_UNIT as u128;
+        u64::try_from(rewards).ok()
+    }
 }
 
 #[account]
@@ -19,9 +47,46 @@ pub struct Listing {
     pub mint: Pubkey,
     pub price: u64,
     pub bump: u8,
-    pub vault_bump: u8,
 }
 
 impl Space for Listing {
-    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1 + 1;
-}
\ No newline at end of file
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+/// What a bid is for: one NFT, or any NFT verified in a collection.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BidTarget {
+    Mint(Pubkey),
+    Collection(Pubkey),
+}
+
+impl BidTarget {
+    /// The mint or collection address, used as the bid's PDA seed.
+    pub fn key(&self) -> Pubkey {
+        match self {
+            BidTarget::Mint(key) | BidTarget::Collection(key) => *key,
+        }
+    }
+
+    /// Seeded next to `key`, so a bid on a mint and one on a collection with
+    /// the same address get different accounts.
+    pub fn tag(&self) -> [u8; 1] {
+        match self {
+            BidTarget::Mint(_) => [0],
+            BidTarget::Collection(_) => [1],
+        }
+    }
+}
+
+/// An offer of `price` lamports, held in the bid account on top of its rent.
+#[account]
+pub struct Bid {
+    pub bidder: Pubkey,
+    pub target: BidTarget,
+    pub price: u64,
+    pub bump: u8,
+}
+
+impl Space for Bid {
+    const INIT_SPACE: usize = 8 + 32 + 1 + 32 + 8 + 1;
+}
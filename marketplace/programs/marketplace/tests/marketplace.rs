@@ -0,0 +1,697 @@
+//! End-to-end tests for listing, delisting, purchasing and bidding on an NFT, run
+//! against the program built natively inside `solana-program-test`. The token
+//! metadata program is not loaded; the metadata and master edition accounts it
+//! would own are written directly.
+
+use anchor_lang::{AccountDeserialize, AnchorSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::{
+    associated_token::{get_associated_token_address, spl_associated_token_account},
+    metadata::mpl_token_metadata::{
+        self,
+        accounts::{MasterEdition, Metadata},
+        types::{Collection, Creator, Key},
+    },
+    token::spl_token::{self, state::Account as TokenAccount},
+};
+use anchor_lang::Space;
+use marketplace::{Bid, BidTarget, MarketplaceError};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    account_info::AccountInfo,
+    bpf_loader,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+const NAME: &str = "launch";
+const FEE: u16 = 100;
+const PRICE: u64 = LAMPORTS_PER_SOL;
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entrypoint ties the account infos to the slice's lifetime.
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    marketplace::entry(program_id, accounts, data)
+}
+
+struct Env {
+    ctx: ProgramTestContext,
+    maker: Keypair,
+    taker: Keypair,
+    marketplace: Pubkey,
+    treasury: Pubkey,
+    rewards: Pubkey,
+    collection: Pubkey,
+}
+
+/// Addresses around one NFT listed on the test marketplace.
+struct Nft {
+    mint: Pubkey,
+    listing: Pubkey,
+    vault: Pubkey,
+    metadata: Pubkey,
+    master_edition: Pubkey,
+}
+
+impl Nft {
+    fn new(env: &Env, mint: Pubkey) -> Self {
+        let (listing, _) = Pubkey::find_program_address(&[env.marketplace.as_ref(), mint.as_ref()], &marketplace::ID);
+        Self {
+            mint,
+            listing,
+            vault: get_associated_token_address(&listing, &mint),
+            metadata: Metadata::find_pda(&mint).0,
+            master_edition: MasterEdition::find_pda(&mint).0,
+        }
+    }
+}
+
+async fn setup() -> Env {
+    let mut program = ProgramTest::new("marketplace", marketplace::ID, processor!(process_instruction));
+    let maker = Keypair::new();
+    let taker = Keypair::new();
+    for user in [&maker, &taker] {
+        program.add_account(
+            user.pubkey(),
+            Account::new(10 * LAMPORTS_PER_SOL, 0, &system_program::ID),
+        );
+    }
+    // only checked to be an executable account at the right address, never invoked
+    program.add_account(
+        mpl_token_metadata::ID,
+        Account { lamports: LAMPORTS_PER_SOL, data: vec![], owner: bpf_loader::ID, executable: true, rent_epoch: 0 },
+    );
+
+    let mut ctx = program.start_with_context().await;
+    let (marketplace, _) = Pubkey::find_program_address(&[b"marketplace", NAME.as_bytes()], &marketplace::ID);
+    let (treasury, _) = Pubkey::find_program_address(&[b"treasury", marketplace.as_ref()], &marketplace::ID);
+    let (rewards, _) = Pubkey::find_program_address(&[b"rewards", marketplace.as_ref()], &marketplace::ID);
+    let admin = ctx.payer.pubkey();
+    let ix = Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::Initialize {
+            admin,
+            marketplace,
+            treasury,
+            rewards,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::Initialize { name: NAME.to_string(), fee: FEE }.data(),
+    };
+    send(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let mut env = Env { ctx, maker, taker, marketplace, treasury, rewards, collection: Pubkey::default() };
+    env.collection = create_mint(&mut env).await;
+    env
+}
+
+/// Sends `ixs` with the context payer paying the fees, so the balances of the
+/// parties only move by what the program does.
+async fn send(ctx: &mut ProgramTestContext, ixs: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+async fn create_mint(env: &mut Env) -> Pubkey {
+    let mint = Keypair::new();
+    let authority = env.ctx.payer.pubkey();
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &authority,
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &authority, None, 0).unwrap(),
+    ];
+    send(&mut env.ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+/// Mints an NFT of the test collection to the maker, with metadata and a
+/// master edition as the token metadata program would leave them.
+async fn mint_nft(env: &mut Env, verified: bool) -> Nft {
+    let mint = create_mint(env).await;
+    let nft = Nft::new(env, mint);
+    let payer = env.ctx.payer.pubkey();
+    let maker = env.maker.pubkey();
+    let ixs = [
+        spl_associated_token_account::instruction::create_associated_token_account(&payer, &maker, &mint, &spl_token::ID),
+        spl_token::instruction::mint_to(&spl_token::ID, &mint, &get_associated_token_address(&maker, &mint), &payer, &[], 1)
+            .unwrap(),
+    ];
+    send(&mut env.ctx, &ixs, &[]).await.unwrap();
+
+    let master_edition = MasterEdition { key: Key::MasterEditionV2, supply: 0, max_supply: Some(0) };
+    set_metadata_account(env, &nft.metadata, metadata(env, &nft, verified).try_to_vec().unwrap()).await;
+    set_metadata_account(env, &nft.master_edition, master_edition.try_to_vec().unwrap()).await;
+    nft
+}
+
+fn metadata(env: &Env, nft: &Nft, verified: bool) -> Metadata {
+    Metadata {
+        key: Key::MetadataV1,
+        update_authority: env.ctx.payer.pubkey(),
+        mint: nft.mint,
+        name: "Launch #1".to_string(),
+        symbol: "LNCH".to_string(),
+        uri: String::new(),
+        seller_fee_basis_points: 0,
+        creators: None,
+        primary_sale_happened: false,
+        is_mutable: true,
+        edition_nonce: None,
+        token_standard: None,
+        collection: Some(Collection { verified, key: env.collection }),
+        uses: None,
+        collection_details: None,
+        programmable_config: None,
+    }
+}
+
+async fn set_metadata_account(env: &mut Env, address: &Pubkey, data: Vec<u8>) {
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &mpl_token_metadata::ID);
+    account.set_data_from_slice(&data);
+    env.ctx.set_account(address, &account);
+}
+
+async fn balance(env: &mut Env, token_account: &Pubkey) -> u64 {
+    let account = env.ctx.banks_client.get_account(*token_account).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+async fn lamports(env: &mut Env, address: &Pubkey) -> u64 {
+    env.ctx.banks_client.get_balance(*address).await.unwrap()
+}
+
+async fn is_closed(env: &mut Env, address: &Pubkey) -> bool {
+    env.ctx.banks_client.get_account(*address).await.unwrap().is_none()
+}
+
+fn assert_error(err: BanksClientError, code: u32) {
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code)),
+    );
+}
+
+fn list_ix(env: &Env, nft: &Nft, price: u64) -> Instruction {
+    let maker = env.maker.pubkey();
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::List {
+            maker,
+            marketplace: env.marketplace,
+            maker_mint: nft.mint,
+            maker_ata: get_associated_token_address(&maker, &nft.mint),
+            vault: nft.vault,
+            listing: nft.listing,
+            collection_mint: env.collection,
+            metadata: nft.metadata,
+            master_edition: nft.master_edition,
+            metadata_program: mpl_token_metadata::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::Listing { price }.data(),
+    }
+}
+
+fn delist_ix(env: &Env, nft: &Nft, maker: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::Delist {
+            maker: *maker,
+            marketplace: env.marketplace,
+            maker_mint: nft.mint,
+            maker_ata: get_associated_token_address(maker, &nft.mint),
+            listing: nft.listing,
+            vault: nft.vault,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::Delist {}.data(),
+    }
+}
+
+fn purchase_ix(env: &Env, nft: &Nft) -> Instruction {
+    purchase_with_ix(env, nft, PRICE, false)
+}
+
+/// A purchase at `price` that passes both rewards accounts when `rewards` is
+/// set.
+fn purchase_with_ix(env: &Env, nft: &Nft, price: u64, rewards: bool) -> Instruction {
+    let taker = env.taker.pubkey();
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::Purchase {
+            taker,
+            maker: env.maker.pubkey(),
+            maker_mint: nft.mint,
+            marketplace: env.marketplace,
+            taker_ata: get_associated_token_address(&taker, &nft.mint),
+            vault: nft.vault,
+            listing: nft.listing,
+            treasury: env.treasury,
+            rewards_mint: env.rewards,
+            taker_rewards_ata: rewards.then(|| get_associated_token_address(&taker, &env.rewards)),
+            maker_rewards_ata: rewards.then(|| get_associated_token_address(&env.maker.pubkey(), &env.rewards)),
+            metadata: nft.metadata,
+            metadata_program: mpl_token_metadata::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::Purchase { price }.data(),
+    }
+}
+
+fn bid_address(env: &Env, target: BidTarget) -> Pubkey {
+    let (bidder, tag, key) = (env.taker.pubkey(), target.tag(), target.key());
+    let seeds = [b"bid".as_ref(), env.marketplace.as_ref(), bidder.as_ref(), &tag, key.as_ref()];
+    Pubkey::find_program_address(&seeds, &marketplace::ID).0
+}
+
+/// The taker bids `price` on `target`.
+fn place_bid_ix(env: &Env, target: BidTarget, price: u64) -> Instruction {
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::PlaceBid {
+            bidder: env.taker.pubkey(),
+            marketplace: env.marketplace,
+            bid: bid_address(env, target),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::PlaceBid { target, price }.data(),
+    }
+}
+
+/// The maker accepts the taker's bid on `target` with `nft`, out of its
+/// listing if `listed`.
+fn accept_bid_ix(env: &Env, nft: &Nft, target: BidTarget, price: u64, listed: bool) -> Instruction {
+    let (maker, taker) = (env.maker.pubkey(), env.taker.pubkey());
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::AcceptBid {
+            seller: maker,
+            bidder: taker,
+            marketplace: env.marketplace,
+            treasury: env.treasury,
+            bid: bid_address(env, target),
+            mint: nft.mint,
+            seller_ata: get_associated_token_address(&maker, &nft.mint),
+            bidder_ata: get_associated_token_address(&taker, &nft.mint),
+            listing: listed.then_some(nft.listing),
+            vault: listed.then_some(nft.vault),
+            metadata: nft.metadata,
+            master_edition: nft.master_edition,
+            metadata_program: mpl_token_metadata::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::AcceptBid { price }.data(),
+    }
+}
+
+#[tokio::test]
+async fn list_and_purchase() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let maker_ata = get_associated_token_address(&env.maker.pubkey(), &nft.mint);
+
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &maker_ata).await, 0);
+    assert_eq!(balance(&mut env, &nft.vault).await, 1);
+
+    let (maker, treasury) = (env.maker.pubkey(), env.treasury);
+    let rent = lamports(&mut env, &nft.listing).await + lamports(&mut env, &nft.vault).await;
+    let maker_before = lamports(&mut env, &maker).await;
+    let treasury_before = lamports(&mut env, &treasury).await;
+
+    let ix = purchase_ix(&env, &nft);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    let taker_ata = get_associated_token_address(&env.taker.pubkey(), &nft.mint);
+    assert_eq!(balance(&mut env, &taker_ata).await, 1);
+    assert!(is_closed(&mut env, &nft.vault).await);
+    assert!(is_closed(&mut env, &nft.listing).await);
+    // both reward rates are zero, so no rewards account was needed
+    let taker_rewards = get_associated_token_address(&env.taker.pubkey(), &env.rewards);
+    assert!(is_closed(&mut env, &taker_rewards).await);
+
+    let fee = PRICE * FEE as u64 / 10_000;
+    assert_eq!(lamports(&mut env, &maker).await - maker_before, PRICE - fee + rent);
+    assert_eq!(lamports(&mut env, &treasury).await - treasury_before, fee);
+}
+
+#[tokio::test]
+async fn admin_withdraws_collected_fees() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    let ix = purchase_ix(&env, &nft);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    let fee = PRICE * FEE as u64 / 10_000;
+    let (marketplace, treasury) = (env.marketplace, env.treasury);
+    let withdraw_ix = |admin: Pubkey, amount: u64| Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::WithdrawTreasury {
+            admin,
+            marketplace,
+            treasury,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::WithdrawTreasury { amount }.data(),
+    };
+    let admin = env.ctx.payer.pubkey();
+    let taker = env.taker.pubkey();
+
+    let ix = withdraw_ix(taker, fee);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    // the treasury keeps its rent exempt minimum
+    let ix = withdraw_ix(admin, fee + 1);
+    let err = send(&mut env.ctx, &[ix], &[]).await.unwrap_err();
+    assert_error(err, MarketplaceError::InsufficientTreasury.into());
+
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let ix = withdraw_ix(admin, fee);
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(treasury_before - lamports(&mut env, &treasury).await, fee);
+}
+
+#[tokio::test]
+async fn purchase_pays_verified_creators() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let (first, second, unverified) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let mut royalties = metadata(&env, &nft, true);
+    royalties.seller_fee_basis_points = 500;
+    royalties.creators = Some(vec![
+        Creator { address: first, verified: true, share: 60 },
+        Creator { address: unverified, verified: false, share: 10 },
+        Creator { address: second, verified: true, share: 30 },
+    ]);
+    set_metadata_account(&mut env, &nft.metadata, royalties.try_to_vec().unwrap()).await;
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    // every verified creator has to be passed, in metadata order
+    let mut ix = purchase_ix(&env, &nft);
+    ix.accounts.extend([AccountMeta::new(second, false), AccountMeta::new(first, false)]);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::InvalidCreator.into());
+    let mut ix = purchase_ix(&env, &nft);
+    ix.accounts.push(AccountMeta::new(first, false));
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::InvalidCreator.into());
+
+    let maker = env.maker.pubkey();
+    let rent = lamports(&mut env, &nft.listing).await + lamports(&mut env, &nft.vault).await;
+    let maker_before = lamports(&mut env, &maker).await;
+    let mut ix = purchase_ix(&env, &nft);
+    ix.accounts.extend([AccountMeta::new(first, false), AccountMeta::new(second, false)]);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    let (fee, royalty) = (PRICE * FEE as u64 / 10_000, PRICE * 500 / 10_000);
+    assert_eq!(lamports(&mut env, &first).await, royalty * 60 / 100);
+    assert_eq!(lamports(&mut env, &second).await, royalty * 30 / 100);
+    assert_eq!(lamports(&mut env, &unverified).await, 0);
+    // the unverified creator's share stays with the maker
+    let proceeds = PRICE - fee - royalty * 90 / 100;
+    assert_eq!(lamports(&mut env, &maker).await - maker_before, proceeds + rent);
+}
+
+#[tokio::test]
+async fn list_rejects_royalty_above_what_the_fee_leaves() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let mut royalties = metadata(&env, &nft, true);
+    royalties.seller_fee_basis_points = 10_000 - FEE + 1;
+    set_metadata_account(&mut env, &nft.metadata, royalties.try_to_vec().unwrap()).await;
+    let ix = list_ix(&env, &nft, PRICE);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::RoyaltyTooHigh.into());
+
+    royalties.seller_fee_basis_points = 10_000 - FEE;
+    set_metadata_account(&mut env, &nft.metadata, royalties.try_to_vec().unwrap()).await;
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    // raising the royalty after listing fails the sale, not with an overflow
+    royalties.seller_fee_basis_points = 10_000;
+    set_metadata_account(&mut env, &nft.metadata, royalties.try_to_vec().unwrap()).await;
+    let ix = purchase_ix(&env, &nft);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::RoyaltyTooHigh.into());
+}
+
+#[tokio::test]
+async fn purchase_needs_metadata() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    env.ctx.set_account(&nft.metadata, &AccountSharedData::default());
+
+    let ix = purchase_ix(&env, &nft);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+}
+
+#[tokio::test]
+async fn purchase_mints_rewards() {
+    let mut env = setup().await;
+    let set_rates_ix = |env: &Env, admin: Pubkey| Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::SetRewardRates { admin, marketplace: env.marketplace }.to_account_metas(None),
+        data: marketplace::instruction::SetRewardRates { taker_rate: 2_000_000, maker_rate: 1_000_000 }.data(),
+    };
+
+    let ix = set_rates_ix(&env, env.taker.pubkey());
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let ix = set_rates_ix(&env, env.ctx.payer.pubkey());
+    send(&mut env.ctx, &[ix], &[]).await.unwrap();
+
+    let nft = mint_nft(&mut env, true).await;
+    let price = 3 * PRICE / 2;
+    let ix = list_ix(&env, &nft, price);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    let ix = purchase_with_ix(&env, &nft, price, false);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::MissingRewardsAccount.into());
+    let ix = purchase_with_ix(&env, &nft, price, true);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    let taker_rewards = get_associated_token_address(&env.taker.pubkey(), &env.rewards);
+    let maker_rewards = get_associated_token_address(&env.maker.pubkey(), &env.rewards);
+    assert_eq!(balance(&mut env, &taker_rewards).await, 3_000_000);
+    assert_eq!(balance(&mut env, &maker_rewards).await, 1_500_000);
+}
+
+#[tokio::test]
+async fn maker_updates_the_price() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let ix = list_ix(&env, &nft, 0);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::InvalidPrice.into());
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    let update_price_ix = |env: &Env, maker: Pubkey, price: u64| Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::UpdatePrice { maker, marketplace: env.marketplace, listing: nft.listing }
+            .to_account_metas(None),
+        data: marketplace::instruction::UpdatePrice { price }.data(),
+    };
+
+    let ix = update_price_ix(&env, env.taker.pubkey(), PRICE / 2);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let ix = update_price_ix(&env, env.maker.pubkey(), 0);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::InvalidPrice.into());
+
+    let ix = update_price_ix(&env, env.maker.pubkey(), 2 * PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    // a purchase signed for the old price is refused
+    let ix = purchase_ix(&env, &nft);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::PriceMismatch.into());
+    assert_eq!(balance(&mut env, &nft.vault).await, 1);
+
+    // the fee is charged on the new price
+    let treasury = env.treasury;
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let ix = purchase_with_ix(&env, &nft, 2 * PRICE, false);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert_eq!(lamports(&mut env, &treasury).await - treasury_before, 2 * PRICE * FEE as u64 / 10_000);
+}
+
+#[tokio::test]
+async fn accept_bid_on_an_unlisted_nft() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let target = BidTarget::Mint(nft.mint);
+    let bid = bid_address(&env, target);
+    let ix = place_bid_ix(&env, target, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(lamports(&mut env, &bid).await, rent.minimum_balance(Bid::INIT_SPACE) + PRICE);
+
+    // the seller names the price they accept
+    let ix = accept_bid_ix(&env, &nft, target, PRICE / 2, false);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::BidMismatch.into());
+
+    let (maker, taker, treasury) = (env.maker.pubkey(), env.taker.pubkey(), env.treasury);
+    let maker_before = lamports(&mut env, &maker).await;
+    let taker_before = lamports(&mut env, &taker).await;
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let ix = accept_bid_ix(&env, &nft, target, PRICE, false);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    let fee = PRICE * FEE as u64 / 10_000;
+    assert_eq!(balance(&mut env, &get_associated_token_address(&taker, &nft.mint)).await, 1);
+    assert!(is_closed(&mut env, &bid).await);
+    assert_eq!(lamports(&mut env, &treasury).await - treasury_before, fee);
+    // the seller pays for the bidder's token account
+    let ata_rent = rent.minimum_balance(TokenAccount::LEN);
+    assert_eq!(lamports(&mut env, &maker).await - maker_before, PRICE - fee - ata_rent);
+    assert_eq!(lamports(&mut env, &taker).await - taker_before, rent.minimum_balance(Bid::INIT_SPACE));
+}
+
+#[tokio::test]
+async fn accept_collection_bid_on_a_listed_nft() {
+    let mut env = setup().await;
+    let target = BidTarget::Collection(env.collection);
+    let ix = place_bid_ix(&env, target, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    // the NFT has to be verified in the collection
+    let unverified = mint_nft(&mut env, false).await;
+    let ix = accept_bid_ix(&env, &unverified, target, PRICE, false);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::BidMismatch.into());
+
+    let nft = mint_nft(&mut env, true).await;
+    let ix = list_ix(&env, &nft, 2 * PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    let ix = accept_bid_ix(&env, &nft, target, PRICE, true);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    let (taker_ata, bid) = (get_associated_token_address(&env.taker.pubkey(), &nft.mint), bid_address(&env, target));
+    assert_eq!(balance(&mut env, &taker_ata).await, 1);
+    assert!(is_closed(&mut env, &nft.vault).await);
+    assert!(is_closed(&mut env, &nft.listing).await);
+    assert!(is_closed(&mut env, &bid).await);
+}
+
+#[tokio::test]
+async fn mint_and_collection_bids_on_one_address_coexist() {
+    let mut env = setup().await;
+    // the collection NFT's own mint is also the collection's address
+    let (on_mint, on_collection) = (BidTarget::Mint(env.collection), BidTarget::Collection(env.collection));
+    let ix = place_bid_ix(&env, on_mint, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    let ix = place_bid_ix(&env, on_collection, 2 * PRICE);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    assert_ne!(bid_address(&env, on_mint), bid_address(&env, on_collection));
+    for (target, price) in [(on_mint, PRICE), (on_collection, 2 * PRICE)] {
+        let account = env.ctx.banks_client.get_account(bid_address(&env, target)).await.unwrap().unwrap();
+        let bid = Bid::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert!(bid.target == target && bid.price == price);
+    }
+}
+
+#[tokio::test]
+async fn cancel_bid_returns_the_sol() {
+    let mut env = setup().await;
+    let target = BidTarget::Collection(env.collection);
+    let taker = env.taker.pubkey();
+    let taker_before = lamports(&mut env, &taker).await;
+    let ix = place_bid_ix(&env, target, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+
+    let bid = bid_address(&env, target);
+    let ix = Instruction {
+        program_id: marketplace::ID,
+        accounts: marketplace::accounts::CancelBid {
+            bidder: taker,
+            marketplace: env.marketplace,
+            bid,
+        }
+        .to_account_metas(None),
+        data: marketplace::instruction::CancelBid {}.data(),
+    };
+    send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap();
+    assert!(is_closed(&mut env, &bid).await);
+    assert_eq!(lamports(&mut env, &taker).await, taker_before);
+}
+
+#[tokio::test]
+async fn delist_returns_the_nft() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, true).await;
+    let maker = env.maker.pubkey();
+    let ix = list_ix(&env, &nft, PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+
+    // only the maker can pull the listing
+    let taker = env.taker.pubkey();
+    let payer = env.ctx.payer.pubkey();
+    let create_ata =
+        spl_associated_token_account::instruction::create_associated_token_account(&payer, &taker, &nft.mint, &spl_token::ID);
+    send(&mut env.ctx, &[create_ata], &[]).await.unwrap();
+    let ix = delist_ix(&env, &nft, &taker);
+    let err = send(&mut env.ctx, &[ix], &[&env.taker]).await.unwrap_err();
+    assert_error(err, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    let ix = delist_ix(&env, &nft, &maker);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &get_associated_token_address(&maker, &nft.mint)).await, 1);
+    assert!(is_closed(&mut env, &nft.vault).await);
+    assert!(is_closed(&mut env, &nft.listing).await);
+
+    // with the vault closed the NFT can be listed again
+    let ix = list_ix(&env, &nft, 2 * PRICE);
+    send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap();
+    assert_eq!(balance(&mut env, &nft.vault).await, 1);
+}
+
+#[tokio::test]
+async fn listing_needs_a_verified_collection() {
+    let mut env = setup().await;
+    let nft = mint_nft(&mut env, false).await;
+
+    let ix = list_ix(&env, &nft, PRICE);
+    let err = send(&mut env.ctx, &[ix], &[&env.maker]).await.unwrap_err();
+    assert_error(err, MarketplaceError::CollectionNotSet.into());
+}
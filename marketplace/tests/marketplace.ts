@@ -4,13 +4,19 @@ import { Marketplace } from "../target/types/marketplace";
 
 describe("marketplace", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.Marketplace as Program<Marketplace>;
 
   it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
+    const tx = await program.methods
+      .initialize("launch", 100)
+      .accountsPartial({
+        admin: provider.wallet.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      })
+      .rpc();
     console.log("Your transaction signature", tx);
   });
 });